@@ -0,0 +1,201 @@
+//! Post-game analysis: grades every move of a game against the engine.
+
+use std::time::Duration;
+
+use crate::engine::Verdict;
+use crate::game::{Player, TicTacToe};
+
+/// How much worse, in heuristic points, a move in an unclear position has to
+/// score than the best one to count as an inaccuracy.
+const INACCURACY_MARGIN: i32 = 64;
+
+/// How a played move compares to the best one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveQuality {
+    /// As good as the engine's choice.
+    Best,
+    /// Keeps the outcome, but wins slower, loses faster or gives away a
+    /// noticeable heuristic edge.
+    Inaccuracy,
+    /// Changes the outcome, e.g. turns a draw into a loss.
+    Blunder,
+}
+
+/// One move of the game with the engine's opinion on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnnotatedMove {
+    pub mv: usize,
+    pub player: Player,
+    pub quality: MoveQuality,
+    /// What the played move leads to, for `player`.
+    pub played: Verdict,
+    /// What the best move leads to, for `player`.
+    pub best: Verdict,
+    pub best_move: usize,
+}
+
+/// Grades each of `moves`, played from `start`, giving the engine
+/// `time_per_move` to score every position. Stops early at an illegal move
+/// or when the game is over.
+pub fn analyze_game(
+    start: &TicTacToe,
+    moves: &[usize],
+    time_per_move: Duration,
+) -> Vec<AnnotatedMove> {
+    let mut tictactoe = start.clone();
+    let mut annotated = Vec::new();
+    for &mv in moves {
+        if !tictactoe.is_move_valid(mv) {
+            break;
+        }
+        let evaluations = tictactoe.analyze_moves(time_per_move);
+        let verdicts: Vec<(usize, Verdict)> = evaluations
+            .iter()
+            .map(|evaluation| {
+                let verdict = tictactoe.verdict(evaluation.score, evaluation.exact);
+                (evaluation.mv, verdict)
+            })
+            .collect();
+        let Some(&(best_move, best)) = verdicts
+            .iter()
+            .max_by_key(|(_, verdict)| sort_key(*verdict))
+        else {
+            break;
+        };
+        let played = verdicts
+            .iter()
+            .find(|(pos, _)| *pos == mv)
+            .map(|(_, verdict)| *verdict)
+            .expect("valid moves are analyzed");
+
+        annotated.push(AnnotatedMove {
+            mv,
+            player: tictactoe.turn_to_move(),
+            quality: grade(played, best),
+            played,
+            best,
+            best_move,
+        });
+        tictactoe.make_move(mv);
+    }
+    annotated
+}
+
+/// How many inaccuracies and blunders `player` made in `analysis`.
+pub fn mistakes(analysis: &[AnnotatedMove], player: Player) -> (usize, usize) {
+    let count = |quality| {
+        analysis
+            .iter()
+            .filter(|annotated| annotated.player == player && annotated.quality == quality)
+            .count()
+    };
+    (count(MoveQuality::Inaccuracy), count(MoveQuality::Blunder))
+}
+
+/// Larger is better for the player making the move.
+fn sort_key(verdict: Verdict) -> (u8, i64) {
+    let detail = match verdict {
+        Verdict::Win(plies) => -(plies as i64),
+        Verdict::Loss(plies) => plies as i64,
+        Verdict::Draw => 0,
+        Verdict::Unclear(score) => score as i64,
+    };
+    (verdict.outcome_rank(), detail)
+}
+
+fn grade(played: Verdict, best: Verdict) -> MoveQuality {
+    if played.outcome_rank() < best.outcome_rank() {
+        return MoveQuality::Blunder;
+    }
+    let worse = match (played, best) {
+        (Verdict::Unclear(played), Verdict::Unclear(best)) => played + INACCURACY_MARGIN < best,
+        _ => sort_key(played) < sort_key(best),
+    };
+    if worse {
+        MoveQuality::Inaccuracy
+    } else {
+        MoveQuality::Best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grades_a_missed_win() {
+        // O loses with 1, then loses faster with 2 instead of blocking at 8,
+        // and X misses the win at 8 with 7.
+        let analysis = analyze_game(
+            &TicTacToe::new(),
+            &[0, 1, 4, 2, 7, 8],
+            Duration::from_secs(5),
+        );
+        let qualities: Vec<MoveQuality> =
+            analysis.iter().map(|annotated| annotated.quality).collect();
+        assert_eq!(
+            qualities,
+            [
+                MoveQuality::Best,
+                MoveQuality::Blunder,
+                MoveQuality::Best,
+                MoveQuality::Inaccuracy,
+                MoveQuality::Blunder,
+                MoveQuality::Best,
+            ]
+        );
+        let missed = analysis[4];
+        assert_eq!(missed.player, Player::One);
+        assert_eq!(missed.played, Verdict::Draw);
+        assert_eq!(missed.best, Verdict::Win(1));
+        assert_eq!(missed.best_move, 8);
+        assert_eq!(analysis[1].played, Verdict::Loss(6));
+        assert_eq!(analysis[1].best, Verdict::Draw);
+
+        assert_eq!(mistakes(&analysis, Player::One), (0, 1));
+        assert_eq!(mistakes(&analysis, Player::Two), (1, 1));
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_game() {
+        let moves = [0, 3, 1, 4, 2, 5];
+        let analysis = analyze_game(&TicTacToe::new(), &moves, Duration::from_secs(5));
+        assert_eq!(analysis.len(), 5);
+        let analysis = analyze_game(&TicTacToe::new(), &[4, 4], Duration::from_secs(5));
+        assert_eq!(analysis.len(), 1);
+    }
+
+    #[test]
+    fn grades_by_outcome_first() {
+        assert_eq!(grade(Verdict::Draw, Verdict::Win(3)), MoveQuality::Blunder);
+        assert_eq!(grade(Verdict::Loss(2), Verdict::Draw), MoveQuality::Blunder);
+        assert_eq!(
+            grade(Verdict::Loss(2), Verdict::Unclear(-500)),
+            MoveQuality::Blunder
+        );
+        assert_eq!(
+            grade(Verdict::Win(5), Verdict::Win(1)),
+            MoveQuality::Inaccuracy
+        );
+        assert_eq!(
+            grade(Verdict::Loss(2), Verdict::Loss(4)),
+            MoveQuality::Inaccuracy
+        );
+        assert_eq!(grade(Verdict::Win(1), Verdict::Win(1)), MoveQuality::Best);
+        assert_eq!(grade(Verdict::Draw, Verdict::Draw), MoveQuality::Best);
+    }
+
+    #[test]
+    fn grades_unclear_positions_with_a_margin() {
+        let best = Verdict::Unclear(100);
+        assert_eq!(grade(Verdict::Unclear(100), best), MoveQuality::Best);
+        assert_eq!(
+            grade(Verdict::Unclear(100 - INACCURACY_MARGIN), best),
+            MoveQuality::Best
+        );
+        assert_eq!(
+            grade(Verdict::Unclear(99 - INACCURACY_MARGIN), best),
+            MoveQuality::Inaccuracy
+        );
+    }
+}
@@ -0,0 +1,576 @@
+//! An HTTP/JSON API for playing games and analyzing positions.
+//!
+//! | Request                          | Answer                                  |
+//! |----------------------------------|-----------------------------------------|
+//! | `POST /games`                    | `201` and the new game                  |
+//! | `GET /games`                     | the ids of all games                    |
+//! | `GET /games/<id>`                | the game                                |
+//! | `DELETE /games/<id>`             | `204`, the game is gone                 |
+//! | `POST /games/<id>/moves`         | the game after the move                 |
+//! | `GET /games/<id>/status`         | whether and how the game ended          |
+//! | `GET /games/<id>/best-move`      | the engine's move and every move's score|
+//! | `GET /games/<id>/record`         | the game as a [record](crate::record)   |
+//! | `GET /games/<id>/watch`          | a WebSocket of the game's updates       |
+//! | `POST /analyze`                  | like `best-move`, for any position      |
+//!
+//! `POST /games` takes an optional body such as
+//! `{"width": 4, "height": 4, "win_length": 3, "first": "o"}` or
+//! `{"position": "x1o/1x1/3 o 3"}` with a position in the
+//! [notation](crate::notation). Either may add `"misere": true` for a game
+//! where completing a line loses. `POST /games/<id>/moves` takes
+//! `{"cell": 4}`, and `POST /analyze` takes `{"position": "..."}`.
+//! `best-move` and `analyze` accept a `time_ms` query parameter for how long
+//! the engine may think.
+//!
+//! A game looks like this, with `board` listing every cell row by row:
+//!
+//! ```json
+//! {"id": 1, "width": 3, "height": 3, "win_length": 3, "misere": false,
+//!  "position": "x1o/1x1/3 o 3", "board": ["x", null, "o", ...],
+//!  "turn": "o", "moves": [0, 2, 4], "legal_moves": [1, 3, 5, 6, 7, 8],
+//!  "status": "ongoing", "winner": null}
+//! ```
+//!
+//! Spectators open a WebSocket on `/games/<id>/watch`. They get the game
+//! as a text message right away and again after every move, until the game
+//! is deleted and the server closes the socket.
+//!
+//! Games played elsewhere, such as over the [network](crate::net), can be
+//! shown to spectators too with [`Api::publish`]. They are listed and
+//! watched like any other game, but their moves can only be made through
+//! the returned [`PublishedGame`].
+//!
+//! `status` is `ongoing`, `won` or `draw`. Scores come as verdicts for the
+//! side to move: `{"outcome": "win", "plies": 3, "text": "wins in 3 moves"}`
+//! with `outcome` one of `win`, `loss`, `draw` and `unclear`; unclear ones
+//! carry a heuristic `score` instead of `plies`. Errors are answered with
+//! `{"error": "..."}`: `400` for bad requests, `404` for unknown games,
+//! `409` for illegal moves and `431` for request lines or headers that are
+//! too large.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use crate::engine::{Verdict, DEFAULT_TIME_BUDGET};
+use crate::game::{check_board, Cell, GameOver, Player, TicTacToe};
+use crate::http::{self, Request, Response};
+use crate::json::Json;
+use crate::record::GameRecord;
+use crate::websocket::{self, Frame};
+
+/// Longest time a client may let the engine think.
+const MAX_THINKING_TIME: Duration = Duration::from_secs(10);
+
+/// The games played through the API.
+#[derive(Debug, Default)]
+pub struct Api {
+    games: Mutex<Games>,
+}
+
+#[derive(Debug, Default)]
+struct Games {
+    next_id: u64,
+    records: BTreeMap<u64, GameRecord>,
+    /// Frames to send to the spectators of each game.
+    watchers: BTreeMap<u64, Vec<Sender<Frame>>>,
+    /// The games played outside the API, see [`Api::publish`].
+    published: BTreeSet<u64>,
+}
+
+impl Games {
+    /// Stores a new game and returns its id.
+    fn add(&mut self, record: GameRecord) -> u64 {
+        self.next_id += 1;
+        self.records.insert(self.next_id, record);
+        self.next_id
+    }
+
+    /// Sends `frame` to every spectator of game `id`, forgetting the ones
+    /// that left.
+    fn broadcast(&mut self, id: u64, frame: &Frame) {
+        if let Some(watchers) = self.watchers.get_mut(&id) {
+            watchers.retain(|watcher| watcher.send(frame.clone()).is_ok());
+        }
+    }
+}
+
+/// A game played outside the API and shown to its spectators, see
+/// [`Api::publish`].
+#[derive(Debug)]
+pub struct PublishedGame {
+    api: Arc<Api>,
+    id: u64,
+}
+
+impl PublishedGame {
+    /// The id spectators watch the game under.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Plays `mv` and sends the game to the spectators. Illegal moves are
+    /// ignored, as are moves after the game was deleted.
+    pub fn play(&self, mv: usize) {
+        let mut games = self.api.games();
+        let Some(record) = games.records.get_mut(&self.id) else {
+            return;
+        };
+        let mut tictactoe = record
+            .replay()
+            .expect("moves are checked before they are recorded");
+        if tictactoe.game_over() != GameOver::OnGoing || !tictactoe.is_move_valid(mv) {
+            return;
+        }
+        tictactoe.make_move(mv);
+        record.push(mv);
+        let game = game_json(self.id, record, &tictactoe);
+        games.broadcast(self.id, &Frame::Text(game.to_string()));
+    }
+}
+
+impl Api {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a game that is played elsewhere, starting from `start`, so
+    /// spectators can watch it. Its moves are made through the returned
+    /// handle; requests to play them are refused.
+    pub fn publish(self: &Arc<Self>, start: &TicTacToe) -> PublishedGame {
+        let mut games = self.games();
+        let id = games.add(GameRecord::new(start));
+        games.published.insert(id);
+        PublishedGame {
+            api: Arc::clone(self),
+            id,
+        }
+    }
+
+    /// Answers requests on `listener` forever, each connection on its own
+    /// thread. A connection that fails to be accepted is skipped, so one bad
+    /// client or a passing shortage of file descriptors does not stop the
+    /// server.
+    pub fn serve(self: Arc<Self>, listener: TcpListener) {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let api = Arc::clone(&self);
+            thread::spawn(move || {
+                let _ = api.serve_connection(stream);
+            });
+        }
+    }
+
+    fn serve_connection(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let response = match Request::read(&mut reader) {
+            Ok(Some(request)) if websocket::is_upgrade(&request) => {
+                return self.watch(&request, reader, stream);
+            }
+            Ok(Some(request)) => self.handle(&request),
+            Ok(None) => return Ok(()),
+            Err(err) => Response::error(http::error_status(&err), err.to_string()),
+        };
+        response.write_to(&mut &stream)
+    }
+
+    /// Sends the updates of the game in `request`'s path over a WebSocket
+    /// until the spectator or the game goes away.
+    fn watch(
+        &self,
+        request: &Request,
+        mut reader: BufReader<TcpStream>,
+        mut stream: TcpStream,
+    ) -> io::Result<()> {
+        let segments: Vec<&str> = request
+            .path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let id = match segments[..] {
+            ["games", id, "watch"] => id.parse::<u64>().ok(),
+            _ => None,
+        };
+        let Some(handshake) = websocket::handshake(request) else {
+            return Response::error(400, "invalid WebSocket handshake").write_to(&mut stream);
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        {
+            let mut games = self.games();
+            let game = id.and_then(|id| games.records.get(&id).map(|record| (id, record)));
+            let Some((id, record)) = game else {
+                return Response::error(404, format!("no such game {}", request.path))
+                    .write_to(&mut stream);
+            };
+            let tictactoe = record
+                .replay()
+                .expect("moves are checked before they are recorded");
+            let game = game_json(id, record, &tictactoe);
+            let _ = sender.send(Frame::Text(game.to_string()));
+            games.watchers.entry(id).or_default().push(sender.clone());
+        }
+        stream.write_all(handshake.as_bytes())?;
+
+        // Spectators only send pings and the closing frame.
+        thread::spawn(move || loop {
+            match websocket::read_frame(&mut reader) {
+                Ok(Frame::Ping(data)) => {
+                    let _ = sender.send(Frame::Pong(data));
+                }
+                Ok(Frame::Close) | Err(_) => {
+                    let _ = sender.send(Frame::Close);
+                    break;
+                }
+                Ok(_) => {}
+            }
+        });
+        for frame in receiver {
+            websocket::write_frame(&mut stream, &frame)?;
+            if frame == Frame::Close {
+                break;
+            }
+        }
+        stream.shutdown(std::net::Shutdown::Both)
+    }
+
+    /// Answers one request.
+    pub fn handle(&self, request: &Request) -> Response {
+        let segments: Vec<&str> = request
+            .path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let method = request.method.as_str();
+        match segments[..] {
+            ["games"] => match method {
+                "GET" => {
+                    let ids: Vec<Json> = self
+                        .games()
+                        .records
+                        .keys()
+                        .map(|&id| Json::Number(id as f64))
+                        .collect();
+                    Response::json(200, &Json::object([("games", Json::Array(ids))]))
+                }
+                "POST" => self.create_game(request),
+                _ => method_not_allowed(),
+            },
+            ["games", id, ref rest @ ..] => {
+                let Ok(id) = id.parse() else {
+                    return Response::error(404, format!("no game {}", id));
+                };
+                self.handle_game(request, id, rest)
+            }
+            ["analyze"] => match method {
+                "POST" => match parse_body(request) {
+                    Ok(body) => match body.get("position").and_then(Json::as_str) {
+                        Some(position) => match TicTacToe::from_notation(position) {
+                            Ok(tictactoe) => analyze(tictactoe, request),
+                            Err(err) => Response::error(400, format!("invalid position: {}", err)),
+                        },
+                        None => Response::error(400, "missing \"position\""),
+                    },
+                    Err(response) => response,
+                },
+                _ => method_not_allowed(),
+            },
+            _ => Response::error(404, format!("no such endpoint {}", request.path)),
+        }
+    }
+
+    fn handle_game(&self, request: &Request, id: u64, rest: &[&str]) -> Response {
+        let method = request.method.as_str();
+        let mut games = self.games();
+        let published = games.published.contains(&id);
+        let Some(record) = games.records.get_mut(&id) else {
+            return Response::error(404, format!("no game {}", id));
+        };
+        let tictactoe = record
+            .replay()
+            .expect("moves are checked before they are recorded");
+        match (method, rest) {
+            ("GET", []) => Response::json(200, &game_json(id, record, &tictactoe)),
+            ("DELETE", []) => {
+                games.records.remove(&id);
+                games.published.remove(&id);
+                games.broadcast(id, &Frame::Close);
+                games.watchers.remove(&id);
+                Response {
+                    status: 204,
+                    content_type: "application/json",
+                    body: String::new(),
+                }
+            }
+            ("POST", ["moves"]) => {
+                let body = match parse_body(request) {
+                    Ok(body) => body,
+                    Err(response) => return response,
+                };
+                let Some(mv) = body.get("cell").and_then(Json::as_usize) else {
+                    return Response::error(400, "missing \"cell\"");
+                };
+                if published {
+                    return Response::error(409, format!("game {} is played elsewhere", id));
+                }
+                if tictactoe.game_over() != GameOver::OnGoing {
+                    return Response::error(409, "the game is over");
+                }
+                if !tictactoe.is_move_valid(mv) {
+                    return Response::error(409, format!("illegal move {}", mv));
+                }
+                let mut tictactoe = tictactoe;
+                tictactoe.make_move(mv);
+                record.push(mv);
+                let game = game_json(id, record, &tictactoe);
+                games.broadcast(id, &Frame::Text(game.to_string()));
+                Response::json(200, &game)
+            }
+            ("GET", ["status"]) => Response::json(200, &status_json(&tictactoe)),
+            ("GET", ["record"]) => Response::text(200, record.to_string()),
+            ("GET", ["best-move"]) => {
+                // Searching can take a while, so other games are not kept
+                // waiting for it.
+                drop(games);
+                analyze(tictactoe, request)
+            }
+            ("GET", ["watch"]) => Response::error(400, "expected a WebSocket upgrade"),
+            (_, [] | ["moves"] | ["status"] | ["record"] | ["best-move"] | ["watch"]) => {
+                method_not_allowed()
+            }
+            _ => Response::error(404, format!("no such endpoint {}", request.path)),
+        }
+    }
+
+    fn create_game(&self, request: &Request) -> Response {
+        let body = match parse_body(request) {
+            Ok(body) => body,
+            Err(response) => return response,
+        };
+        let misere = match body.get("misere") {
+            None => None,
+            Some(Json::Bool(misere)) => Some(*misere),
+            Some(_) => return Response::error(400, "\"misere\" must be true or false"),
+        };
+        let start = match body.get("position") {
+            Some(position) => {
+                let Some(position) = position.as_str() else {
+                    return Response::error(400, "\"position\" must be a string");
+                };
+                match TicTacToe::from_notation(position) {
+                    Ok(tictactoe) => tictactoe,
+                    Err(err) => return Response::error(400, format!("invalid position: {}", err)),
+                }
+            }
+            None => {
+                let number = |name, default| match body.get(name) {
+                    Some(value) => value.as_usize(),
+                    None => Some(default),
+                };
+                let (Some(width), Some(height)) = (number("width", 3), number("height", 3)) else {
+                    return Response::error(400, "\"width\" and \"height\" must be numbers");
+                };
+                let Some(win_length) = number("win_length", width.min(height).min(5)) else {
+                    return Response::error(400, "\"win_length\" must be a number");
+                };
+                if let Err(err) = check_board(width, height, win_length) {
+                    return Response::error(400, err.to_string());
+                }
+                let first = match body.get("first").map(|first| first.as_str()) {
+                    None | Some(Some("x")) => Player::One,
+                    Some(Some("o")) => Player::Two,
+                    Some(_) => return Response::error(400, "\"first\" must be \"x\" or \"o\""),
+                };
+                TicTacToe::with_size(width, height, win_length).with_first_player(first)
+            }
+        };
+        let start = match misere {
+            Some(misere) => start.with_misere(misere),
+            None => start,
+        };
+
+        let record = GameRecord::new(&start);
+        let id = self.games().add(record.clone());
+        Response::json(201, &game_json(id, &record, &start))
+    }
+
+    fn games(&self) -> MutexGuard<'_, Games> {
+        self.games
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The engine's move in `tictactoe` and the verdict on every legal move,
+/// thinking for the request's `time_ms`, split between the two.
+fn analyze(mut tictactoe: TicTacToe, request: &Request) -> Response {
+    let time = match request.query("time_ms") {
+        Some(millis) => match millis.parse() {
+            Ok(millis) => Duration::from_millis(millis).min(MAX_THINKING_TIME),
+            Err(_) => return Response::error(400, "time_ms must be a number"),
+        },
+        None => DEFAULT_TIME_BUDGET,
+    };
+    let result = tictactoe.search(u32::MAX, Some(time / 2));
+    let evaluations: Vec<Json> = tictactoe
+        .analyze_moves(time / 2)
+        .into_iter()
+        .map(|evaluation| {
+            Json::object([
+                ("cell", evaluation.mv.into()),
+                (
+                    "verdict",
+                    verdict_json(tictactoe.verdict(evaluation.score, evaluation.exact)),
+                ),
+            ])
+        })
+        .collect();
+    let verdict = match result.best_move {
+        Some(_) => verdict_json(tictactoe.verdict(result.score, result.exact)),
+        None => Json::Null,
+    };
+    Response::json(
+        200,
+        &Json::object([
+            ("best_move", result.best_move.into()),
+            ("verdict", verdict),
+            ("depth", result.depth.into()),
+            ("evaluations", Json::Array(evaluations)),
+        ]),
+    )
+}
+
+fn verdict_json(verdict: Verdict) -> Json {
+    let (outcome, plies, score) = match verdict {
+        Verdict::Win(plies) => ("win", Some(plies), None),
+        Verdict::Loss(plies) => ("loss", Some(plies), None),
+        Verdict::Draw => ("draw", None, None),
+        Verdict::Unclear(score) => ("unclear", None, Some(score)),
+    };
+    let mut members = vec![("outcome".to_string(), outcome.into())];
+    if let Some(plies) = plies {
+        members.push(("plies".to_string(), plies.into()));
+    }
+    if let Some(score) = score {
+        members.push(("score".to_string(), score.into()));
+    }
+    members.push(("text".to_string(), verdict.to_string().into()));
+    Json::Object(members)
+}
+
+/// A game as described in the module docs.
+pub fn game_json(id: u64, record: &GameRecord, tictactoe: &TicTacToe) -> Json {
+    let board: Vec<Json> = (0..tictactoe.size())
+        .map(|pos| match tictactoe.cell(pos) {
+            Cell::Empty => Json::Null,
+            Cell::Occupied(player) => player_json(player),
+        })
+        .collect();
+    let mut game = Json::object([
+        ("id", Json::Number(id as f64)),
+        ("width", tictactoe.width().into()),
+        ("height", tictactoe.height().into()),
+        ("win_length", tictactoe.win_length().into()),
+        ("misere", tictactoe.is_misere().into()),
+        ("position", tictactoe.to_notation().into()),
+        ("board", Json::Array(board)),
+        ("turn", player_json(tictactoe.turn_to_move())),
+        ("moves", record.moves().to_vec().into()),
+        ("legal_moves", legal_moves(tictactoe).into()),
+    ]);
+    if let (Json::Object(members), Json::Object(status)) = (&mut game, status_json(tictactoe)) {
+        members.extend(status);
+    }
+    game
+}
+
+/// `status` and `winner` of a game, as described in the module docs.
+pub fn status_json(tictactoe: &TicTacToe) -> Json {
+    let (status, winner) = match tictactoe.game_over() {
+        GameOver::OnGoing => ("ongoing", Json::Null),
+        GameOver::Draw => ("draw", Json::Null),
+        GameOver::Winner(player) => ("won", player_json(player)),
+    };
+    Json::object([("status", status.into()), ("winner", winner)])
+}
+
+fn legal_moves(tictactoe: &TicTacToe) -> Vec<usize> {
+    if tictactoe.game_over() == GameOver::OnGoing {
+        tictactoe.get_all_moves()
+    } else {
+        Vec::new()
+    }
+}
+
+fn player_json(player: Player) -> Json {
+    match player {
+        Player::One => "x".into(),
+        Player::Two => "o".into(),
+    }
+}
+
+/// The request body as JSON; an empty body counts as `{}`.
+fn parse_body(request: &Request) -> Result<Json, Response> {
+    if request.body.trim().is_empty() {
+        return Ok(Json::Object(Vec::new()));
+    }
+    match request.body.parse::<Json>() {
+        Ok(body @ Json::Object(_)) => Ok(body),
+        Ok(_) => Err(Response::error(400, "the body must be a JSON object")),
+        Err(err) => Err(Response::error(400, err.to_string())),
+    }
+}
+
+fn method_not_allowed() -> Response {
+    Response::error(405, "method not allowed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: Vec::new(),
+            headers: Vec::new(),
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn publishes_games_played_elsewhere() {
+        let api = Arc::new(Api::new());
+        let game = api.publish(&TicTacToe::new());
+        let path = format!("/games/{}", game.id());
+        let (sender, receiver) = mpsc::channel();
+        api.games().watchers.insert(game.id(), vec![sender]);
+
+        game.play(4);
+        game.play(4);
+        let Ok(Frame::Text(update)) = receiver.try_recv() else {
+            panic!("the spectator got no update");
+        };
+        assert!(update.contains("\"moves\":[4]"), "{}", update);
+        assert!(receiver.try_recv().is_err());
+
+        let response = api.handle(&request("GET", &path, ""));
+        assert_eq!(response.status, 200);
+        assert!(response.body.contains("\"moves\":[4]"), "{}", response.body);
+        let response = api.handle(&request(
+            "POST",
+            &format!("{}/moves", path),
+            "{\"cell\": 0}",
+        ));
+        assert_eq!(response.status, 409);
+
+        api.handle(&request("DELETE", &path, ""));
+        game.play(0);
+        assert_eq!(api.handle(&request("GET", &path, "")).status, 404);
+    }
+}
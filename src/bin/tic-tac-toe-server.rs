@@ -0,0 +1,293 @@
+//! A game server: players connect over TCP, find each other through lobbies
+//! or matchmaking, and play any number of games at once. The server checks
+//! every move, so clients cannot cheat. The protocol is described in
+//! `tic_tac_toe::net`. With `--watch`, every game is also published on an
+//! HTTP API for spectators, see `tic_tac_toe::api`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use tic_tac_toe::api::Api;
+use tic_tac_toe::net::{self, Connection, Message};
+use tic_tac_toe::record::parse_variant;
+use tic_tac_toe::rng::Rng;
+use tic_tac_toe::{GameOver, Player, TicTacToe};
+
+const USAGE: &str = "\
+Usage:
+    tic-tac-toe-server [[ADDRESS:]PORT] [--watch <[ADDRESS:]PORT>]
+        serve games (default: 0.0.0.0:7711), and with --watch let
+        spectators follow them on the HTTP API at that address, e.g.
+        ws://localhost:8080/games/<id>/watch";
+
+/// A board size and win length, e.g. `(3, 3, 3)`.
+type Variant = (usize, usize, usize);
+
+/// Players waiting for an opponent.
+#[derive(Default)]
+struct Lobbies {
+    next_lobby: u32,
+    next_game: u32,
+    /// Lobbies opened with `create`, by id.
+    open: BTreeMap<u32, (Variant, Connection)>,
+    /// The player waiting in the matchmaking queue of each variant.
+    queued: HashMap<Variant, Connection>,
+}
+
+impl Lobbies {
+    /// Forgets the waiting players who have left, so nobody is listed or
+    /// paired with them.
+    fn drop_closed(&mut self) {
+        self.open
+            .retain(|_, (_, connection)| !connection.is_closed());
+        self.queued.retain(|_, connection| !connection.is_closed());
+    }
+}
+
+fn main() {
+    let mut address = None;
+    let mut watch = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "help" | "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--watch" if watch.is_none() => match args.next() {
+                Some(value) => watch = Some(net::complete_address(&value, "0.0.0.0")),
+                None => usage_error(),
+            },
+            _ if address.is_none() && !arg.starts_with("--") => {
+                address = Some(net::complete_address(&arg, "0.0.0.0"))
+            }
+            _ => usage_error(),
+        }
+    }
+    let address =
+        address.unwrap_or_else(|| net::complete_address(&net::DEFAULT_PORT.to_string(), "0.0.0.0"));
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("error: could not listen on {}: {}", address, err);
+            std::process::exit(1);
+        }
+    };
+    println!("Serving games on {}", address);
+    let spectators = watch.map(|watch| {
+        let listener = TcpListener::bind(&watch).unwrap_or_else(|err| {
+            eprintln!("error: could not listen on {}: {}", watch, err);
+            std::process::exit(1);
+        });
+        println!("Spectators can watch the games on http://{}", watch);
+        let api = Arc::new(Api::new());
+        let server = Arc::clone(&api);
+        thread::spawn(move || server.serve(listener));
+        api
+    });
+
+    let lobbies = Arc::new(Mutex::new(Lobbies::default()));
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let lobbies = Arc::clone(&lobbies);
+        let spectators = spectators.clone();
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map_or_else(|_| "?".to_string(), |peer| peer.to_string());
+            if let Err(err) = serve(stream, &lobbies, spectators.as_ref()) {
+                println!("{}: {}", peer, err);
+            }
+        });
+    }
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+/// Answers one player's requests until they are waiting for an opponent or
+/// in a game, and plays the game if this player completes a pairing. Games
+/// are published to `spectators` if there are any.
+fn serve(
+    stream: TcpStream,
+    lobbies: &Mutex<Lobbies>,
+    spectators: Option<&Arc<Api>>,
+) -> io::Result<()> {
+    let mut connection = Connection::new(stream)?;
+    loop {
+        let message = match connection.receive() {
+            Ok(message) => message,
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                connection.send(&Message::Error(err.to_string()))?;
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        match message {
+            Message::List => {
+                let mut lobbies = lock(lobbies);
+                lobbies.drop_closed();
+                let open: Vec<Message> = lobbies
+                    .open
+                    .iter()
+                    .map(|(&id, &(variant, _))| Message::Lobby {
+                        id,
+                        variant: format_variant(variant),
+                    })
+                    .collect();
+                drop(lobbies);
+                for lobby in &open {
+                    connection.send(lobby)?;
+                }
+                connection.send(&Message::End)?;
+            }
+            Message::Create(variant) => {
+                let Some(variant) = check_variant(&mut connection, &variant)? else {
+                    continue;
+                };
+                let mut lobbies = lock(lobbies);
+                let id = lobbies.next_lobby;
+                lobbies.next_lobby += 1;
+                connection.send(&Message::Lobby {
+                    id,
+                    variant: format_variant(variant),
+                })?;
+                lobbies.open.insert(id, (variant, connection));
+                return Ok(());
+            }
+            Message::Join(id) => {
+                let lobby = {
+                    let mut lobbies = lock(lobbies);
+                    lobbies.drop_closed();
+                    lobbies.open.remove(&id)
+                };
+                match lobby {
+                    Some((variant, host)) => {
+                        return play(lobbies, spectators, [host, connection], variant)
+                    }
+                    None => connection.send(&Message::Error(format!("no open lobby {}", id)))?,
+                }
+            }
+            Message::Queue(variant) => {
+                let Some(variant) = check_variant(&mut connection, &variant)? else {
+                    continue;
+                };
+                let mut waiting = lock(lobbies);
+                waiting.drop_closed();
+                match waiting.queued.remove(&variant) {
+                    Some(opponent) => {
+                        drop(waiting);
+                        return play(lobbies, spectators, [opponent, connection], variant);
+                    }
+                    None => {
+                        waiting.queued.insert(variant, connection);
+                        return Ok(());
+                    }
+                }
+            }
+            Message::Bye => return Ok(()),
+            other => connection.send(&Message::Error(format!("unexpected '{}'", other)))?,
+        }
+    }
+}
+
+/// Plays a game of `variant` between two players, who get their sides at
+/// random.
+fn play(
+    lobbies: &Mutex<Lobbies>,
+    spectators: Option<&Arc<Api>>,
+    mut players: [Connection; 2],
+    variant: Variant,
+) -> io::Result<()> {
+    let id = {
+        let mut lobbies = lock(lobbies);
+        lobbies.next_game += 1;
+        lobbies.next_game
+    };
+    if Player::random(&mut Rng::from_entropy()) == Player::Two {
+        players.swap(0, 1);
+    }
+    let (width, height, win_length) = variant;
+    let mut tictactoe = TicTacToe::with_size(width, height, win_length);
+    println!("game {}: started on {}", id, format_variant(variant));
+    let published = spectators.map(|api| api.publish(&tictactoe));
+    if let Some(published) = &published {
+        println!("game {}: watch it at /games/{}/watch", id, published.id());
+    }
+
+    for player in [Player::One, Player::Two] {
+        players[player.index()].send(&Message::Game {
+            start: Box::new(tictactoe.clone()),
+            guest: player,
+        })?;
+    }
+    for connection in &mut players {
+        match connection.receive()? {
+            Message::Ready => {}
+            other => return Err(unexpected(&other)),
+        }
+    }
+
+    while tictactoe.game_over() == GameOver::OnGoing {
+        let turn = tictactoe.turn_to_move();
+        match players[turn.index()].receive()? {
+            Message::Move(mv) if tictactoe.is_move_valid(mv) => {
+                tictactoe.make_move(mv);
+                if let Some(published) = &published {
+                    published.play(mv);
+                }
+                players[turn.opponent().index()].send(&Message::Move(mv))?;
+            }
+            Message::Move(mv) => {
+                players[turn.index()].send(&Message::Error(format!("illegal move {}", mv)))?
+            }
+            Message::Bye => {
+                println!("game {}: abandoned", id);
+                return Ok(());
+            }
+            other => {
+                players[turn.index()].send(&Message::Error(format!("unexpected '{}'", other)))?
+            }
+        }
+    }
+    let result = match tictactoe.game_over() {
+        GameOver::Winner(Player::One) => "X won",
+        GameOver::Winner(Player::Two) => "O won",
+        _ => "draw",
+    };
+    println!("game {}: {}", id, result);
+    Ok(())
+}
+
+/// Parses a requested variant, telling the player if it is not playable.
+fn check_variant(connection: &mut Connection, text: &str) -> io::Result<Option<Variant>> {
+    match parse_variant(text) {
+        Ok(variant) => Ok(Some(variant)),
+        Err(_) => {
+            connection.send(&Message::Error(format!("invalid variant '{}'", text)))?;
+            Ok(None)
+        }
+    }
+}
+
+fn format_variant((width, height, win_length): Variant) -> String {
+    format!("{}x{}/{}", width, height, win_length)
+}
+
+fn unexpected(message: &Message) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unexpected message '{}'", message),
+    )
+}
+
+fn lock(lobbies: &Mutex<Lobbies>) -> std::sync::MutexGuard<'_, Lobbies> {
+    lobbies
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,104 @@
+//! Fixed-size bit sets used to store the board.
+
+use std::ops::{BitAnd, BitOr, Not};
+
+/// Largest number of cells a [`Bitboard`] (and so a board) can hold.
+pub const MAX_CELLS: usize = 256;
+
+const WORDS: usize = MAX_CELLS / 64;
+
+/// A set of cell indices below [`MAX_CELLS`], one bit per cell.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Bitboard([u64; WORDS]);
+
+impl Bitboard {
+    pub const EMPTY: Self = Self([0; WORDS]);
+
+    /// A bitboard with the first `cells` bits set.
+    pub fn full(cells: usize) -> Self {
+        let mut bitboard = Self::EMPTY;
+        for (i, word) in bitboard.0.iter_mut().enumerate() {
+            let bits = cells.saturating_sub(i * 64).min(64);
+            *word = if bits == 64 {
+                u64::MAX
+            } else {
+                (1 << bits) - 1
+            };
+        }
+        bitboard
+    }
+
+    pub fn contains(&self, pos: usize) -> bool {
+        self.0[pos / 64] & (1 << (pos % 64)) != 0
+    }
+
+    pub fn set(&mut self, pos: usize) {
+        self.0[pos / 64] |= 1 << (pos % 64);
+    }
+
+    pub fn clear(&mut self, pos: usize) {
+        self.0[pos / 64] &= !(1 << (pos % 64));
+    }
+
+    /// Whether every cell of `other` is also in `self`.
+    pub fn contains_all(&self, other: &Self) -> bool {
+        *self & *other == *other
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+
+    /// Number of cells in the set.
+    pub fn count(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// The cell indices in the set, in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(i, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Self;
+
+    fn bitand(mut self, rhs: Self) -> Self {
+        for (word, other) in self.0.iter_mut().zip(rhs.0) {
+            *word &= other;
+        }
+        self
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Self;
+
+    fn bitor(mut self, rhs: Self) -> Self {
+        for (word, other) in self.0.iter_mut().zip(rhs.0) {
+            *word |= other;
+        }
+        self
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Self;
+
+    fn not(mut self) -> Self {
+        for word in self.0.iter_mut() {
+            *word = !*word;
+        }
+        self
+    }
+}
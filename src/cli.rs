@@ -0,0 +1,736 @@
+//! Command line arguments of the terminal front end.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tic_tac_toe::engine::DEFAULT_TIME_BUDGET;
+use tic_tac_toe::game::check_board;
+use tic_tac_toe::net;
+use tic_tac_toe::{Controller, Difficulty, Player, TicTacToe};
+
+pub const USAGE: &str = "\
+Usage:
+    tic-tac-toe                 open the interactive menu
+    tic-tac-toe play [OPTIONS]  start a game right away
+    tic-tac-toe replay <FILE>   step through a saved game
+    tic-tac-toe analyze <FILE>  grade every move of a saved game
+    tic-tac-toe engine          speak the engine protocol on stdin and stdout
+    tic-tac-toe serve [PORT] [--bind <ADDRESS>]
+                                serve the HTTP/JSON API on PORT (default:
+                                8080), only to this machine unless --bind
+                                names another address, e.g. 0.0.0.0
+    tic-tac-toe match <PLAYER> <PLAYER> [OPTIONS]
+                                play computer players against each other
+    tic-tac-toe help            show this message
+
+Options for play:
+    --mode <hvh|hvc|cvc>        human vs human, human vs computer or
+                                computer vs computer (default: hvc)
+    --variant <VARIANT>         classic, misere where completing a line
+                                loses, ultimate for nine boards in a 3x3
+                                grid, or gomoku for five in a row on 15x15
+                                (default: classic); ultimate and gomoku
+                                only take --mode, the difficulties, --side,
+                                --first, --seed and --symbols
+    --exact-five                in gomoku, only exactly five in a row win,
+                                not six or more
+    --swap                      in gomoku, the first player places three
+                                stones and the second then picks a side
+    --size <W>x<H> | <N>        board size (default: 3x3)
+    --win <K>                   marks in a row needed to win
+                                (default: the shorter side, at most 5)
+    --difficulty <LEVEL>        random, easy, medium or perfect, for every
+                                computer player (default: perfect)
+    --x-difficulty <LEVEL>      difficulty of the computer playing X
+    --o-difficulty <LEVEL>      difficulty of the computer playing O
+    --x-engine <COMMAND>        let an external engine play X, e.g.
+                                \"tic-tac-toe engine\"
+    --o-engine <COMMAND>        let an external engine play O
+    --side <x|o>                the side the human plays in hvc (default: x)
+    --first <x|o|random>        who moves first (default: x)
+    --seed <N>                  seed for the computer's random choices
+    --symbols <AB>              symbols drawn for X and O, two different
+                                characters that are not digits (default: XO)
+    --position <NOTATION>       start from this position instead of an empty
+                                board, e.g. \"x1o/1x1/3 o 3\"; replaces
+                                --size, --win and --first
+    --load <FILE>               continue a saved game; replaces --position
+    --save <FILE>               save the game to FILE when it ends
+    --analyze                   grade every move once the game ends
+    --host <[ADDRESS:]PORT>     wait for another player to --join over TCP;
+                                they play the computer's side in hvc and O
+                                otherwise (default port: 7711)
+    --join <HOST[:PORT]>        play a game hosted with --host; the host
+                                picks the board and sides
+    --server <HOST[:PORT]>      play someone on a tic-tac-toe-server, on the
+                                board given by --size and --win
+    --lobby <new|ID>            with --server, open a lobby or join lobby ID
+                                instead of waiting for the next player
+    --watch <[ADDRESS:]PORT>    with --host, --join or --server, let
+                                spectators follow the game on the HTTP API
+                                at this address, only to this machine
+                                unless ADDRESS is given
+
+A match PLAYER is a difficulty (random, easy, medium or perfect) or
+engine:<COMMAND> for an external engine. Options for match:
+    --games <N>                 number of games, the players take turns
+                                starting (default: 100)
+    --size <W>x<H> | <N>        board size (default: 3x3)
+    --win <K>                   marks in a row needed to win
+    --movetime <MS>             thinking time per move of external engines
+                                (default: 2000)
+    --seed <N>                  seed for the computer's random choices";
+
+/// What the program was asked to do.
+#[derive(Debug)]
+pub enum Command {
+    Menu,
+    Play(Box<GameOptions>),
+    Replay(PathBuf),
+    Analyze(PathBuf),
+    Engine,
+    Match(Box<MatchOptions>),
+    /// Serve the HTTP API on this address.
+    Serve(String),
+    Help,
+}
+
+/// The rules a game is played by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Classic,
+    /// Completing a line loses, see [`TicTacToe::with_misere`].
+    Misere,
+    /// Nine boards in a 3x3 grid, see [`tic_tac_toe::ultimate`].
+    Ultimate,
+    /// Five in a row on 15x15, see [`tic_tac_toe::gomoku`].
+    Gomoku,
+}
+
+impl Variant {
+    /// The name used by `--variant`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Classic => "classic",
+            Variant::Misere => "misere",
+            Variant::Ultimate => "ultimate",
+            Variant::Gomoku => "gomoku",
+        }
+    }
+}
+
+/// Everything needed to start a game without asking questions.
+#[derive(Debug)]
+pub struct GameOptions {
+    pub variant: Variant,
+    pub controllers: [Controller; 2],
+    /// Commands starting the external engines of [`Controller::Engine`] sides.
+    pub engines: [Option<String>; 2],
+    pub width: usize,
+    pub height: usize,
+    pub win_length: usize,
+    /// `None` picks the first player at random.
+    pub first: Option<Player>,
+    pub seed: Option<u64>,
+    pub symbols: (char, char),
+    /// Position to start from, replacing the board size and first player.
+    pub position: Option<TicTacToe>,
+    /// Saved game to continue, replacing `position`.
+    pub load: Option<PathBuf>,
+    pub save: Option<PathBuf>,
+    pub analyze: bool,
+    pub network: Option<Network>,
+    /// Where to publish a network game for spectators.
+    pub watch: Option<String>,
+    /// In gomoku, whether only exactly five in a row win.
+    pub exact_five: bool,
+    /// In gomoku, whether to play the swap opening.
+    pub swap: bool,
+}
+
+/// How a network game is set up.
+#[derive(Debug)]
+pub enum Network {
+    /// Listen on this address for the other player.
+    Host(String),
+    /// Connect to the game hosted at this address.
+    Join(String),
+    /// Ask the game server at this address for a game with this request.
+    Server(String, net::Message),
+}
+
+/// A player of a match, before any external engine is started.
+#[derive(Debug)]
+pub enum ContestantSpec {
+    Computer(Difficulty),
+    /// Command starting an external engine.
+    Engine(String),
+}
+
+/// Everything needed to run a match.
+#[derive(Debug)]
+pub struct MatchOptions {
+    pub contestants: [ContestantSpec; 2],
+    pub games: u32,
+    pub width: usize,
+    pub height: usize,
+    pub win_length: usize,
+    pub movetime: Duration,
+    pub seed: Option<u64>,
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        None => Ok(Command::Menu),
+        Some("play") => parse_play(args).map(|options| Command::Play(Box::new(options))),
+        Some("replay") => match (args.next(), args.next()) {
+            (Some(path), None) => Ok(Command::Replay(path.into())),
+            _ => Err("replay expects exactly one file".to_string()),
+        },
+        Some("analyze") => match (args.next(), args.next()) {
+            (Some(path), None) => Ok(Command::Analyze(path.into())),
+            _ => Err("analyze expects exactly one file".to_string()),
+        },
+        Some("engine") => match args.next() {
+            None => Ok(Command::Engine),
+            Some(_) => Err("engine takes no arguments".to_string()),
+        },
+        Some("serve") => parse_serve(args).map(Command::Serve),
+        Some("match") => parse_match(args).map(|options| Command::Match(Box::new(options))),
+        Some("help" | "--help" | "-h") => Ok(Command::Help),
+        Some(other) => Err(format!("unknown command '{}'", other)),
+    }
+}
+
+fn parse_play(mut args: impl Iterator<Item = String>) -> Result<GameOptions, String> {
+    let mut mode = "hvc".to_string();
+    let mut variant = Variant::Classic;
+    let mut size = None;
+    let mut win_length = None;
+    let mut difficulties = [Difficulty::default(); 2];
+    let mut engines = [None, None];
+    let mut side = Player::One;
+    let mut first = Some(Player::One);
+    let mut seed = None;
+    let mut symbols = ('X', 'O');
+    let mut position = None;
+    let mut load = None;
+    let mut save = None;
+    let mut analyze = false;
+    let mut exact_five = false;
+    let mut swap = false;
+    let mut network = None;
+    let mut server = None;
+    let mut lobby = None;
+    let mut watch = None;
+
+    while let Some(arg) = args.next() {
+        let switch = match arg.as_str() {
+            "--analyze" => Some(&mut analyze),
+            "--exact-five" => Some(&mut exact_five),
+            "--swap" => Some(&mut swap),
+            _ => None,
+        };
+        if let Some(switch) = switch {
+            *switch = true;
+            continue;
+        }
+        let (flag, mut value) = split_flag(arg, &mut args);
+        match flag.as_str() {
+            "--mode" => mode = value()?,
+            "--variant" => variant = parse_variant(&value()?)?,
+            "--size" => size = Some(parse_size(&value()?)?),
+            "--win" => win_length = Some(parse_number(&flag, &value()?)?),
+            "--difficulty" => difficulties = [parse_difficulty(&value()?)?; 2],
+            "--x-difficulty" => difficulties[0] = parse_difficulty(&value()?)?,
+            "--o-difficulty" => difficulties[1] = parse_difficulty(&value()?)?,
+            "--x-engine" => engines[0] = Some(value()?),
+            "--o-engine" => engines[1] = Some(value()?),
+            "--side" => side = parse_player(&value()?)?,
+            "--first" => {
+                let value = value()?;
+                first = if value == "random" {
+                    None
+                } else {
+                    Some(parse_player(&value)?)
+                };
+            }
+            "--seed" => seed = Some(parse_number(&flag, &value()?)?),
+            "--symbols" => {
+                let value = value()?;
+                // Digits would be mistaken for the cell numbers on the board.
+                let valid = |c: char| !c.is_whitespace() && !c.is_ascii_digit();
+                match value.chars().collect::<Vec<char>>()[..] {
+                    [x, o] if x != o && valid(x) && valid(o) => symbols = (x, o),
+                    _ => {
+                        return Err(format!(
+                            "--symbols needs two different symbols that are not digits, got '{}'",
+                            value
+                        ))
+                    }
+                }
+            }
+            "--position" => {
+                let value = value()?;
+                let parsed = TicTacToe::from_notation(&value)
+                    .map_err(|err| format!("invalid position '{}': {}", value, err))?;
+                position = Some(parsed);
+            }
+            "--load" => load = Some(PathBuf::from(value()?)),
+            "--save" => save = Some(PathBuf::from(value()?)),
+            "--host" => network = Some(Network::Host(net::complete_address(&value()?, "0.0.0.0"))),
+            "--join" => {
+                network = Some(Network::Join(net::complete_address(&value()?, "localhost")))
+            }
+            "--server" => server = Some(net::complete_address(&value()?, "localhost")),
+            "--lobby" => {
+                let value = value()?;
+                lobby = Some(if value == "new" {
+                    None
+                } else {
+                    Some(parse_number(&flag, &value)?)
+                });
+            }
+            "--watch" => watch = Some(net::complete_address(&value()?, "127.0.0.1")),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+
+    if watch.is_some() && network.is_none() && server.is_none() {
+        return Err("--watch needs --host, --join or --server".to_string());
+    }
+    if variant != Variant::Gomoku && (exact_five || swap) {
+        return Err("--exact-five and --swap need --variant gomoku".to_string());
+    }
+    if matches!(variant, Variant::Ultimate | Variant::Gomoku) {
+        let unsupported = [
+            ("--size", size.is_some()),
+            ("--win", win_length.is_some()),
+            ("--x-engine/--o-engine", engines.iter().any(Option::is_some)),
+            ("--position", position.is_some()),
+            ("--load", load.is_some()),
+            ("--save", save.is_some()),
+            ("--analyze", analyze),
+            (
+                "--host/--join/--server",
+                network.is_some() || server.is_some(),
+            ),
+        ];
+        if let Some((flag, _)) = unsupported.iter().find(|(_, used)| *used) {
+            return Err(format!(
+                "{} cannot be used with --variant {}",
+                flag,
+                variant.name()
+            ));
+        }
+    }
+    if variant == Variant::Misere {
+        // Saved games keep their own rules, and the server only pairs
+        // players for classic games.
+        if load.is_some() {
+            return Err("--load cannot be used with --variant misere".to_string());
+        }
+        if server.is_some() {
+            return Err("--server cannot be used with --variant misere".to_string());
+        }
+        position = position.map(|position| position.with_misere(true));
+    }
+    let computers = difficulties.map(Controller::Computer);
+    let mut controllers = match mode.as_str() {
+        "hvh" => [Controller::Human; 2],
+        "hvc" if side == Player::One => [Controller::Human, computers[1]],
+        "hvc" => [computers[0], Controller::Human],
+        "cvc" => computers,
+        _ => return Err(format!("unknown mode '{}', expected hvh, hvc or cvc", mode)),
+    };
+    if let Some(Network::Host(_)) = network {
+        let remote = match mode.as_str() {
+            "hvc" => side.opponent(),
+            _ => Player::Two,
+        };
+        controllers[remote.index()] = Controller::Remote;
+    }
+    for (controller, engine) in controllers.iter_mut().zip(&engines) {
+        if engine.is_some() {
+            *controller = Controller::Engine;
+        }
+    }
+    let (width, height, win_length) = board(size, win_length)?;
+    if let Some(server) = server {
+        let variant = format!("{}x{}/{}", width, height, win_length);
+        let request = match lobby {
+            None => net::Message::Queue(variant),
+            Some(None) => net::Message::Create(variant),
+            Some(Some(id)) => net::Message::Join(id),
+        };
+        network = Some(Network::Server(server, request));
+    } else if lobby.is_some() {
+        return Err("--lobby needs --server".to_string());
+    }
+
+    Ok(GameOptions {
+        variant,
+        controllers,
+        engines,
+        width,
+        height,
+        win_length,
+        first,
+        seed,
+        symbols,
+        position,
+        load,
+        save,
+        analyze,
+        network,
+        watch,
+        exact_five,
+        swap,
+    })
+}
+
+/// Parses the arguments of `serve` into the address to listen on.
+fn parse_serve(mut args: impl Iterator<Item = String>) -> Result<String, String> {
+    let mut port = None;
+    let mut bind = "127.0.0.1".to_string();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if port.is_some() {
+                return Err("serve expects at most one port".to_string());
+            }
+            port = Some(parse_number::<u16>("the port", &arg)?);
+            continue;
+        }
+        let (flag, mut value) = split_flag(arg, &mut args);
+        match flag.as_str() {
+            "--bind" => bind = value()?,
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+
+    let port = port.unwrap_or(8080);
+    // IPv6 addresses need brackets before the port.
+    if bind.contains(':') && !bind.starts_with('[') {
+        Ok(format!("[{}]:{}", bind, port))
+    } else {
+        Ok(format!("{}:{}", bind, port))
+    }
+}
+
+fn parse_match(mut args: impl Iterator<Item = String>) -> Result<MatchOptions, String> {
+    let mut contestants = Vec::new();
+    let mut games = 100;
+    let mut size = None;
+    let mut win_length = None;
+    let mut movetime = DEFAULT_TIME_BUDGET;
+    let mut seed = None;
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            contestants.push(parse_contestant(&arg)?);
+            continue;
+        }
+        let (flag, mut value) = split_flag(arg, &mut args);
+        match flag.as_str() {
+            "--games" => games = parse_number(&flag, &value()?)?,
+            "--size" => size = Some(parse_size(&value()?)?),
+            "--win" => win_length = Some(parse_number(&flag, &value()?)?),
+            "--movetime" => movetime = Duration::from_millis(parse_number(&flag, &value()?)?),
+            "--seed" => seed = Some(parse_number(&flag, &value()?)?),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+
+    let contestants: [ContestantSpec; 2] = contestants
+        .try_into()
+        .map_err(|_| "match expects exactly two players".to_string())?;
+    let (width, height, win_length) = board(size, win_length)?;
+    Ok(MatchOptions {
+        contestants,
+        games,
+        width,
+        height,
+        win_length,
+        movetime,
+        seed,
+    })
+}
+
+fn parse_contestant(value: &str) -> Result<ContestantSpec, String> {
+    match value.strip_prefix("engine:") {
+        Some(command) if !command.trim().is_empty() => {
+            Ok(ContestantSpec::Engine(command.to_string()))
+        }
+        Some(_) => Err("engine: needs a command".to_string()),
+        None => parse_difficulty(value).map(ContestantSpec::Computer),
+    }
+}
+
+/// Applies the defaults to `--size` and `--win` and checks the board.
+fn board(
+    size: Option<(usize, usize)>,
+    win_length: Option<usize>,
+) -> Result<(usize, usize, usize), String> {
+    let (width, height) = size.unwrap_or((3, 3));
+    let win_length = win_length.unwrap_or(width.min(height).min(5));
+    check_board(width, height, win_length).map_err(|err| err.to_string())
+}
+
+/// Splits `--flag=value` into the flag and a closure returning its value,
+/// which is the next argument if the flag has no `=value`.
+fn split_flag<'a>(
+    arg: String,
+    args: &'a mut impl Iterator<Item = String>,
+) -> (String, impl FnMut() -> Result<String, String> + 'a) {
+    let (flag, mut inline_value) = match arg.split_once('=') {
+        Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+        None => (arg, None),
+    };
+    let missing = format!("missing value for {}", flag);
+    let value = move || {
+        inline_value
+            .take()
+            .or_else(|| args.next())
+            .ok_or_else(|| missing.clone())
+    };
+    (flag, value)
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got '{}'", flag, value))
+}
+
+fn parse_size(value: &str) -> Result<(usize, usize), String> {
+    match value.split_once('x') {
+        Some((width, height)) => Ok((
+            parse_number("--size", width)?,
+            parse_number("--size", height)?,
+        )),
+        None => {
+            let side = parse_number("--size", value)?;
+            Ok((side, side))
+        }
+    }
+}
+
+fn parse_variant(value: &str) -> Result<Variant, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "classic" => Ok(Variant::Classic),
+        "misere" | "misère" => Ok(Variant::Misere),
+        "ultimate" => Ok(Variant::Ultimate),
+        "gomoku" => Ok(Variant::Gomoku),
+        _ => Err(format!(
+            "unknown variant '{}', expected classic, misere, ultimate or gomoku",
+            value
+        )),
+    }
+}
+
+fn parse_difficulty(value: &str) -> Result<Difficulty, String> {
+    value.parse::<Difficulty>().map_err(|err| err.to_string())
+}
+
+fn parse_player(value: &str) -> Result<Player, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "x" | "1" => Ok(Player::One),
+        "o" | "2" => Ok(Player::Two),
+        _ => Err(format!("unknown side '{}', expected x or o", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &str) -> Result<Command, String> {
+        parse(args.split_whitespace().map(str::to_string))
+    }
+
+    fn play(args: &str) -> Result<GameOptions, String> {
+        match parse_args(&format!("play {}", args))? {
+            Command::Play(options) => Ok(*options),
+            command => panic!("expected play, got {:?}", command),
+        }
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert!(matches!(parse_args(""), Ok(Command::Menu)));
+        assert!(matches!(parse_args("help"), Ok(Command::Help)));
+        assert!(matches!(parse_args("engine"), Ok(Command::Engine)));
+        assert!(matches!(
+            parse_args("replay game.pgn"),
+            Ok(Command::Replay(_))
+        ));
+        assert!(parse_args("replay").is_err());
+        assert!(parse_args("engine extra").is_err());
+        assert!(parse_args("fly").is_err());
+    }
+
+    #[test]
+    fn parses_play_defaults() {
+        let options = play("").unwrap();
+        assert_eq!(options.variant, Variant::Classic);
+        assert_eq!(
+            (options.width, options.height, options.win_length),
+            (3, 3, 3)
+        );
+        assert_eq!(
+            options.controllers,
+            [Controller::Human, Controller::Computer(Difficulty::Perfect)]
+        );
+        assert_eq!(options.first, Some(Player::One));
+        assert_eq!(options.symbols, ('X', 'O'));
+    }
+
+    #[test]
+    fn parses_play_options() {
+        let options = play(
+            "--mode=hvc --side o --size 5x4 --win=4 --x-difficulty easy --first random --seed 7",
+        )
+        .unwrap();
+        assert_eq!(
+            (options.width, options.height, options.win_length),
+            (5, 4, 4)
+        );
+        assert_eq!(
+            options.controllers,
+            [Controller::Computer(Difficulty::Easy), Controller::Human]
+        );
+        assert_eq!(options.first, None);
+        assert_eq!(options.seed, Some(7));
+
+        let options = play("--join example.org --watch 8080").unwrap();
+        assert_eq!(options.watch.as_deref(), Some("127.0.0.1:8080"));
+
+        let options = play("--size 7").unwrap();
+        assert_eq!(
+            (options.width, options.height, options.win_length),
+            (7, 7, 5)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_play_options() {
+        for args in [
+            "--mode vvv",
+            "--size 0x3",
+            "--size 17x16",
+            "--size 4294967296x4294967296",
+            "--size 3 --win 4",
+            "--win",
+            "--seed many",
+            "--unknown 1",
+            "--variant chess",
+            "--variant ultimate --size 4",
+            "--swap",
+            "--lobby new",
+            "--watch 8080",
+        ] {
+            assert!(play(args).is_err(), "{}", args);
+        }
+    }
+
+    #[test]
+    fn parses_symbols() {
+        assert_eq!(play("--symbols AB").unwrap().symbols, ('A', 'B'));
+        assert_eq!(play("--symbols ●○").unwrap().symbols, ('●', '○'));
+        for symbols in ["A", "ABC", "AA", "1O", "X7", "X "] {
+            assert!(
+                play(&format!("--symbols={}", symbols)).is_err(),
+                "{:?}",
+                symbols
+            );
+        }
+    }
+
+    #[test]
+    fn parses_serve() {
+        for (args, address) in [
+            ("serve", "127.0.0.1:8080"),
+            ("serve 9000", "127.0.0.1:9000"),
+            ("serve --bind 0.0.0.0", "0.0.0.0:8080"),
+            ("serve --bind=::1 9000", "[::1]:9000"),
+        ] {
+            match parse_args(args) {
+                Ok(Command::Serve(parsed)) => assert_eq!(parsed, address),
+                other => panic!("{}: {:?}", args, other),
+            }
+        }
+        for args in [
+            "serve 1 2",
+            "serve port",
+            "serve 70000",
+            "serve --bind",
+            "serve --host x",
+        ] {
+            assert!(parse_args(args).is_err(), "{}", args);
+        }
+    }
+
+    #[test]
+    fn parses_match() {
+        let Ok(Command::Match(options)) =
+            parse_args("match easy engine:tic-tac-toe --games 4 --size 4 --movetime=50")
+        else {
+            panic!("expected a match");
+        };
+        assert!(matches!(
+            options.contestants,
+            [
+                ContestantSpec::Computer(Difficulty::Easy),
+                ContestantSpec::Engine(_)
+            ]
+        ));
+        assert_eq!(options.games, 4);
+        assert_eq!(
+            (options.width, options.height, options.win_length),
+            (4, 4, 4)
+        );
+        assert_eq!(options.movetime, Duration::from_millis(50));
+        for args in [
+            "match easy",
+            "match easy hard",
+            "match easy easy easy",
+            "match easy engine:",
+        ] {
+            assert!(parse_args(args).is_err(), "{}", args);
+        }
+    }
+
+    #[test]
+    fn splits_flags() {
+        let mut args = vec!["4".to_string()].into_iter();
+        let mut split = |arg: &str| {
+            let (flag, mut value) = split_flag(arg.to_string(), &mut args);
+            (flag, value())
+        };
+        assert_eq!(split("--win=5"), ("--win".to_string(), Ok("5".to_string())));
+        assert_eq!(split("--win"), ("--win".to_string(), Ok("4".to_string())));
+        assert_eq!(
+            split("--win"),
+            (
+                "--win".to_string(),
+                Err("missing value for --win".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn checks_boards() {
+        assert_eq!(board(None, None), Ok((3, 3, 3)));
+        assert_eq!(board(Some((15, 15)), None), Ok((15, 15, 5)));
+        assert!(board(Some((3, 3)), Some(3)).is_ok());
+        assert!(board(Some((16, 16)), Some(16)).is_ok());
+        assert!(board(Some((5, 1)), Some(5)).is_ok());
+        assert!(board(Some((3, 3)), Some(4)).is_err());
+        assert!(board(Some((3, 3)), Some(0)).is_err());
+        assert!(board(Some((0, 3)), Some(3)).is_err());
+        assert!(board(Some((17, 16)), Some(5)).is_err());
+        assert!(board(Some((usize::MAX, 2)), Some(3)).is_err());
+    }
+}
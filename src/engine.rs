@@ -0,0 +1,396 @@
+//! The computer player for [`TicTacToe`], searching with the shared
+//! [alpha-beta search](crate::search).
+//!
+//! Scores are from [`Player::One`]'s point of view: positive scores favour
+//! `Player::One`, negative ones `Player::Two`. A won game scores
+//! [`WIN_SCORE`] minus the number of moves played, so quicker wins score
+//! higher and the losing side prefers to lose as late as possible. A drawn
+//! game scores `0`, and unfinished positions at the search horizon get a
+//! heuristic score in between.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::bitboard::Bitboard;
+use crate::game::{GameOver, Player, TicTacToe};
+use crate::rng::Rng;
+use crate::search::{self, Position, DECIDED_SCORE};
+use crate::transposition::TranspositionTable;
+
+pub use crate::search::SearchResult;
+
+/// Score of a game won without any moves, see the module docs.
+pub const WIN_SCORE: i32 = 1_000_000_000;
+
+/// Heuristic scores are clamped to stay well below [`DECIDED_SCORE`].
+const HEURISTIC_LIMIT: i64 = WIN_SCORE as i64 / 2;
+
+/// How long [`TicTacToe::best_move`] may search.
+pub const DEFAULT_TIME_BUDGET: Duration = Duration::from_secs(2);
+
+/// How many plies the [`Difficulty::Easy`] computer looks ahead.
+pub(crate) const EASY_DEPTH: u32 = 2;
+
+/// Chance, out of 100, that the [`Difficulty::Medium`] computer plays a
+/// random move instead of the best one.
+pub(crate) const MEDIUM_BLUNDER_PERCENT: u64 = 25;
+
+/// How well the computer plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Difficulty {
+    /// Plays any legal move.
+    Random,
+    /// Only looks two plies ahead: takes wins and blocks immediate threats.
+    Easy,
+    /// Plays perfectly most of the time but sometimes blunders.
+    Medium,
+    /// Never loses a won or drawn position.
+    #[default]
+    Perfect,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Random,
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Perfect,
+    ];
+
+    /// The lowercase name used by [`FromStr`] and [`Display`](fmt::Display).
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Random => "random",
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Perfect => "perfect",
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error returned when parsing an unknown [`Difficulty`] name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDifficultyError(String);
+
+impl fmt::Display for ParseDifficultyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown difficulty '{}', expected random, easy, medium or perfect",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseDifficultyError {}
+
+impl FromStr for Difficulty {
+    type Err = ParseDifficultyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Difficulty::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseDifficultyError(s.to_string()))
+    }
+}
+
+/// Who makes the moves for one side of a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controller {
+    /// Moves are entered by a person.
+    Human,
+    /// Moves are picked by [`TicTacToe::computer_move`].
+    Computer(Difficulty),
+    /// Moves are picked by an engine in another process, see
+    /// [`ExternalEngine`](crate::protocol::ExternalEngine).
+    Engine,
+    /// Moves are made by a player on another machine, see
+    /// [`Connection`](crate::net::Connection).
+    Remote,
+}
+
+/// What a score means for the side to move, see [`TicTacToe::verdict`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The side to move wins, with the game ending after this many more moves.
+    Win(u32),
+    /// The side to move loses, with the game ending after this many more moves.
+    Loss(u32),
+    Draw,
+    /// The search did not see the end of the game. The heuristic score is
+    /// from the side to move's point of view, so positive is good for them.
+    Unclear(i32),
+}
+
+impl Verdict {
+    /// Orders verdicts by outcome only: wins above draws and unclear
+    /// positions, which are above losses.
+    pub fn outcome_rank(self) -> u8 {
+        match self {
+            Verdict::Win(_) => 2,
+            Verdict::Draw | Verdict::Unclear(_) => 1,
+            Verdict::Loss(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verdict::Win(1) => write!(f, "wins right away"),
+            Verdict::Win(plies) => write!(f, "wins in {} moves", plies),
+            Verdict::Loss(plies) => write!(f, "loses in {} moves", plies),
+            Verdict::Draw => write!(f, "draws"),
+            Verdict::Unclear(score) => write!(f, "unclear ({:+})", score),
+        }
+    }
+}
+
+/// The score of one legal move, see [`TicTacToe::analyze_moves`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveEvaluation {
+    pub mv: usize,
+    /// Score of the position after `mv`.
+    pub score: i32,
+    pub exact: bool,
+}
+
+impl TicTacToe {
+    /// Scores the position: exactly for finished games, heuristically
+    /// otherwise.
+    ///
+    /// The heuristic counts the lines each player can still complete,
+    /// weighting them by how many marks are already on them. Wins that
+    /// can no longer be stopped are scored like finished games: a player to
+    /// move with a line one mark short of complete wins next move, and a
+    /// player missing two different cells to complete a line, against an
+    /// opponent without such a threat, wins the move after.
+    ///
+    /// In a [misère](TicTacToe::with_misere) game the heuristic is turned
+    /// around, as marks on a line are a liability there, and the only sure
+    /// result is a player to move who completes a line wherever they play,
+    /// losing next move.
+    pub fn evaluate(&self) -> i32 {
+        match self.game_over() {
+            GameOver::Draw => return 0,
+            GameOver::Winner(player) => return self.win_score(player, 0),
+            GameOver::OnGoing => {}
+        }
+
+        let stones = [self.stones(Player::One), self.stones(Player::Two)];
+        // The empty cells that would complete a line for each player.
+        let mut threats = [Bitboard::EMPTY; 2];
+        let mut score: i64 = 0;
+        for (mask, ends) in self.win_masks().iter().zip(self.win_mask_ends()) {
+            let counts = [(stones[0] & *mask).count(), (stones[1] & *mask).count()];
+            for player in [Player::One, Player::Two] {
+                let (own, other) = (counts[player.index()], counts[player.opponent().index()]);
+                if own == 0 || other > 0 {
+                    continue;
+                }
+                // With exact lengths, a line next to an own mark would
+                // become too long and not win.
+                let overline = self.is_exact_length()
+                    && ends
+                        .iter()
+                        .flatten()
+                        .any(|&end| stones[player.index()].contains(end));
+                if own + 1 == self.win_length() && !overline {
+                    threats[player.index()] =
+                        threats[player.index()] | (*mask & !stones[player.index()]);
+                }
+                let weight = 1i64 << (3 * (own - 1)).min(40);
+                score += if player == Player::One {
+                    weight
+                } else {
+                    -weight
+                };
+            }
+        }
+
+        let to_move = self.turn_to_move();
+        if self.is_misere() {
+            let empty = Bitboard::full(self.size()) & !(stones[0] | stones[1]);
+            if threats[to_move.index()].contains_all(&empty) {
+                return self.win_score(to_move.opponent(), 1);
+            }
+            return (-score).clamp(-HEURISTIC_LIMIT, HEURISTIC_LIMIT) as i32;
+        }
+        if !threats[to_move.index()].is_empty() {
+            return self.win_score(to_move, 1);
+        }
+        if threats[to_move.opponent().index()].count() > 1 {
+            return self.win_score(to_move.opponent(), 2);
+        }
+        score.clamp(-HEURISTIC_LIMIT, HEURISTIC_LIMIT) as i32
+    }
+
+    fn plies(&self) -> i32 {
+        self.move_count() as i32
+    }
+
+    /// The score of `winner` winning `plies` moves from now.
+    fn win_score(&self, winner: Player, plies: i32) -> i32 {
+        let score = WIN_SCORE - self.plies() - plies;
+        match winner {
+            Player::One => score,
+            Player::Two => -score,
+        }
+    }
+
+    /// For a score of a won game as returned by [`search`](Self::search),
+    /// how many more moves the game lasts from this position. `None` if the
+    /// score does not prove a win for either side.
+    pub fn plies_to_win(&self, score: i32) -> Option<u32> {
+        if score.abs() >= DECIDED_SCORE {
+            Some((WIN_SCORE - score.abs() - self.plies()).max(0) as u32)
+        } else {
+            None
+        }
+    }
+
+    /// Interprets `score`, a score of this position or of the position after
+    /// one of its moves, from the point of view of the side to move.
+    pub fn verdict(&self, score: i32, exact: bool) -> Verdict {
+        let own_score = match self.turn_to_move() {
+            Player::One => score,
+            Player::Two => -score,
+        };
+        match self.plies_to_win(score) {
+            Some(plies) if own_score > 0 => Verdict::Win(plies),
+            Some(plies) => Verdict::Loss(plies),
+            None if exact && score == 0 => Verdict::Draw,
+            None => Verdict::Unclear(own_score),
+        }
+    }
+
+    /// Searches the position `depth` plies deep and returns its score within
+    /// the `alpha`..`beta` window. Positions still going on at the horizon
+    /// are scored with [`evaluate`](Self::evaluate). Results are stored in
+    /// `table` under the position's canonical hash and reused by later
+    /// searches.
+    pub fn minimax(
+        &mut self,
+        table: &mut TranspositionTable,
+        depth: u32,
+        alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        search::alpha_beta(self, table, depth, alpha, beta)
+    }
+
+    /// Iterative deepening: searches one ply deeper each iteration, up to
+    /// `max_depth` plies, until `time_budget` runs out. The result of the
+    /// last completed iteration is returned, and a depth-1 search is always
+    /// finished so there is a move to play, even if `max_depth` is 0.
+    pub fn search(&mut self, max_depth: u32, time_budget: Option<Duration>) -> SearchResult {
+        search::search(self, max_depth, time_budget)
+    }
+
+    /// The strongest move for the side to move found within
+    /// [`DEFAULT_TIME_BUDGET`], or `None` if the board is full. Small boards
+    /// are searched to the end, so the move is perfect there.
+    pub fn best_move(&mut self) -> Option<usize> {
+        self.search(u32::MAX, Some(DEFAULT_TIME_BUDGET)).best_move
+    }
+
+    /// Scores every legal move, splitting `time_budget` evenly between them.
+    /// Small boards are searched to the end, so the scores are exact there.
+    /// A finished game has no moves to score.
+    pub fn analyze_moves(&mut self, time_budget: Duration) -> Vec<MoveEvaluation> {
+        if self.game_over() != GameOver::OnGoing {
+            return Vec::new();
+        }
+        let moves = self.get_all_moves();
+        let budget_per_move = time_budget / moves.len().max(1) as u32;
+        moves
+            .into_iter()
+            .map(|mv| {
+                self.make_move(mv);
+                let result = self.search(u32::MAX, Some(budget_per_move));
+                self.undo_move(mv);
+                MoveEvaluation {
+                    mv,
+                    score: result.score,
+                    exact: result.exact,
+                }
+            })
+            .collect()
+    }
+
+    /// Picks a move for the side to move at the given `difficulty`, or
+    /// `None` if the board is full.
+    pub fn computer_move(&mut self, difficulty: Difficulty, rng: &mut Rng) -> Option<usize> {
+        match difficulty {
+            Difficulty::Random => rng.choose(&self.get_all_moves()).copied(),
+            Difficulty::Easy => {
+                let mut table = TranspositionTable::new();
+                let maximizing = self.turn_to_move() == Player::One;
+                let mut evaluations_of_moves = Vec::new();
+                for pos in self.get_all_moves() {
+                    self.make_move(pos);
+                    let eval = self.minimax(&mut table, EASY_DEPTH - 1, i32::MIN, i32::MAX);
+                    self.undo_move(pos);
+                    evaluations_of_moves.push((pos, if maximizing { eval } else { -eval }));
+                }
+                let best = evaluations_of_moves.iter().map(|(_, eval)| *eval).max()?;
+                let best_moves: Vec<usize> = evaluations_of_moves
+                    .iter()
+                    .filter(|(_, eval)| *eval == best)
+                    .map(|(pos, _)| *pos)
+                    .collect();
+                rng.choose(&best_moves).copied()
+            }
+            Difficulty::Medium => {
+                if rng.chance(MEDIUM_BLUNDER_PERCENT, 100) {
+                    self.computer_move(Difficulty::Random, rng)
+                } else {
+                    self.best_move()
+                }
+            }
+            Difficulty::Perfect => self.best_move(),
+        }
+    }
+}
+
+impl Position for TicTacToe {
+    fn to_move(&self) -> Player {
+        self.turn_to_move()
+    }
+
+    fn is_over(&self) -> bool {
+        self.game_over() != GameOver::OnGoing
+    }
+
+    fn search_moves(&self) -> Vec<usize> {
+        self.get_all_moves()
+    }
+
+    fn play(&mut self, mv: usize) {
+        self.make_move(mv);
+    }
+
+    fn take_back(&mut self, mv: usize) {
+        self.undo_move(mv);
+    }
+
+    fn evaluate(&self) -> i32 {
+        TicTacToe::evaluate(self)
+    }
+
+    fn key(&self) -> Option<u64> {
+        Some(self.canonical_hash())
+    }
+
+    fn plies_left(&self) -> u32 {
+        self.get_all_moves().len() as u32
+    }
+}
@@ -0,0 +1,557 @@
+use std::fmt;
+
+use crate::bitboard::{Bitboard, MAX_CELLS};
+use crate::rng::Rng;
+
+const ZOBRIST_SEED: u64 = 0x5EED_7AC7_0E5E_ED00;
+
+/// Maps `(x, y)` to its image under a symmetry, given the largest `x` and `y`.
+type Transform = fn(usize, usize, usize, usize) -> (usize, usize);
+
+/// One of the two sides of a game. `One` moves first unless the game says
+/// otherwise (see [`TicTacToe::with_first_player`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+    One,
+    Two,
+}
+
+impl Player {
+    /// The other side.
+    pub fn opponent(self) -> Self {
+        match self {
+            Player::One => Player::Two,
+            Player::Two => Player::One,
+        }
+    }
+
+    /// Either player, with equal chance.
+    pub fn random(rng: &mut Rng) -> Self {
+        if rng.chance(1, 2) {
+            Player::One
+        } else {
+            Player::Two
+        }
+    }
+
+    /// `0` for [`Player::One`] and `1` for [`Player::Two`], handy for
+    /// indexing per-player arrays.
+    pub fn index(self) -> usize {
+        match self {
+            Player::One => 0,
+            Player::Two => 1,
+        }
+    }
+}
+
+/// The content of a single square.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cell {
+    Empty,
+    Occupied(Player),
+}
+
+/// Result of checking a position for the end of the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOver {
+    /// The given player completed a line, or made the opponent complete
+    /// one in a misère game.
+    Winner(Player),
+    /// The board is full and nobody completed a line.
+    Draw,
+    /// There are still moves to play.
+    OnGoing,
+}
+
+/// A board size that cannot be played, see [`check_board`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidBoard {
+    pub width: usize,
+    pub height: usize,
+    pub win_length: usize,
+}
+
+impl fmt::Display for InvalidBoard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid board {}x{} with {} in a row (at most {} cells)",
+            self.width, self.height, self.win_length, MAX_CELLS
+        )
+    }
+}
+
+impl std::error::Error for InvalidBoard {}
+
+/// Checks that a `width`×`height` board with `win_length` in a row can be
+/// played: it has between 1 and [`MAX_CELLS`] cells, and `win_length` is at
+/// least 1 and fits on its longer side. Returns the size unchanged if so.
+pub fn check_board(
+    width: usize,
+    height: usize,
+    win_length: usize,
+) -> Result<(usize, usize, usize), InvalidBoard> {
+    let playable = width
+        .checked_mul(height)
+        .is_some_and(|cells| (1..=MAX_CELLS).contains(&cells))
+        && (1..=width.max(height)).contains(&win_length);
+    if !playable {
+        return Err(InvalidBoard {
+            width,
+            height,
+            win_length,
+        });
+    }
+    Ok((width, height, win_length))
+}
+
+/// A tic-tac-toe position on a `width`×`height` board where `win_length`
+/// marks in a row (horizontally, vertically or diagonally) win.
+///
+/// Cells are addressed by index, row by row, starting at 0 in the top left.
+/// `player_1` and `player_2` are only the symbols used to draw each side.
+///
+/// In a misère game (see [`with_misere`](Self::with_misere)) completing a
+/// line loses instead. With [`with_exact_length`](Self::with_exact_length)
+/// only lines of exactly `win_length` marks count, not longer ones.
+///
+/// The board is stored as one [`Bitboard`] per player. Every possible line of
+/// `win_length` cells is precomputed as a mask, and the number of completed
+/// lines per player is kept up to date by `make_move`/`undo_move`, so
+/// `game_over` never has to scan the board.
+///
+/// Whose turn it is is stored explicitly rather than derived from the
+/// stones, so positions where one side has extra marks (handicaps, passes,
+/// `Player::Two` starting) are fine.
+///
+/// A Zobrist hash is kept up to date for every symmetry of the board (the
+/// eight rotations and reflections of a square, or the four of a rectangle),
+/// so [`canonical_hash`](Self::canonical_hash) is the same for all positions
+/// that are just rotated or mirrored versions of each other.
+#[derive(Debug, Clone)]
+pub struct TicTacToe {
+    player_1: char,
+    player_2: char,
+    width: usize,
+    height: usize,
+    win_length: usize,
+    /// Whether completing a line loses instead of winning.
+    misere: bool,
+    /// Whether lines longer than `win_length` are ignored.
+    exact_length: bool,
+    stones: [Bitboard; 2],
+    win_masks: Vec<Bitboard>,
+    /// For every mask, the cells just before and after it on its line.
+    mask_ends: Vec<[Option<usize>; 2]>,
+    /// Indices into `win_masks` of the lines going through each cell.
+    masks_by_cell: Vec<Vec<usize>>,
+    completed_lines: [usize; 2],
+    side_to_move: Player,
+    move_count: usize,
+    zobrist_keys: Vec<[u64; 2]>,
+    zobrist_side: u64,
+    /// For every symmetry, the cell each cell is mapped to.
+    symmetries: Vec<Vec<usize>>,
+    /// The hash of the board under each of `symmetries`.
+    hashes: Vec<u64>,
+}
+
+impl TicTacToe {
+    /// Creates the classic 3×3, three-in-a-row game.
+    pub fn new() -> Self {
+        Self::with_size(3, 3, 3)
+    }
+
+    /// Creates an empty `width`×`height` board where `win_length` in a row wins.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`check_board`] refuses the size.
+    pub fn with_size(width: usize, height: usize, win_length: usize) -> Self {
+        if let Err(err) = check_board(width, height, win_length) {
+            panic!("{}", err);
+        }
+        let player_1 = 'X';
+        let player_2 = 'O';
+        let mut win_masks = Vec::new();
+        let mut mask_ends = Vec::new();
+        let mut masks_by_cell = vec![Vec::new(); width * height];
+        let directions = [(1, 0), (0, 1), (1, 1), (-1, 1)];
+        for pos in 0..width * height {
+            let (x, y) = ((pos % width) as isize, (pos / width) as isize);
+            for (dx, dy) in directions {
+                let reach = win_length as isize - 1;
+                let (end_x, end_y) = (x + dx * reach, y + dy * reach);
+                if end_x < 0 || end_x as usize >= width || end_y as usize >= height {
+                    continue;
+                }
+                let mut mask = Bitboard::EMPTY;
+                for step in 0..win_length as isize {
+                    let cell = (y + dy * step) as usize * width + (x + dx * step) as usize;
+                    mask.set(cell);
+                    masks_by_cell[cell].push(win_masks.len());
+                }
+                win_masks.push(mask);
+                let cell_at = |x: isize, y: isize| {
+                    (x >= 0 && (x as usize) < width && y >= 0 && (y as usize) < height)
+                        .then(|| y as usize * width + x as usize)
+                };
+                mask_ends.push([cell_at(x - dx, y - dy), cell_at(end_x + dx, end_y + dy)]);
+            }
+        }
+
+        let mut rng = Rng::new(ZOBRIST_SEED);
+        let zobrist_keys = (0..width * height)
+            .map(|_| [rng.next_u64(), rng.next_u64()])
+            .collect();
+        let zobrist_side = rng.next_u64();
+        let (w, h) = (width - 1, height - 1);
+        let mut transforms: Vec<Transform> = vec![
+            |x, y, _, _| (x, y),
+            |x, y, w, _| (w - x, y),
+            |x, y, _, h| (x, h - y),
+            |x, y, w, h| (w - x, h - y),
+        ];
+        if width == height {
+            transforms.extend_from_slice(&[
+                |x, y, _, _| (y, x),
+                |x, y, w, _| (w - y, x),
+                |x, y, _, h| (y, h - x),
+                |x, y, w, h| (w - y, h - x),
+            ]);
+        }
+        let symmetries: Vec<Vec<usize>> = transforms
+            .iter()
+            .map(|transform| {
+                (0..width * height)
+                    .map(|pos| {
+                        let (x, y) = transform(pos % width, pos / width, w, h);
+                        y * width + x
+                    })
+                    .collect()
+            })
+            .collect();
+        let hashes = vec![0; symmetries.len()];
+
+        Self {
+            player_1,
+            player_2,
+            width,
+            height,
+            win_length,
+            misere: false,
+            exact_length: false,
+            stones: [Bitboard::EMPTY; 2],
+            win_masks,
+            mask_ends,
+            masks_by_cell,
+            completed_lines: [0; 2],
+            side_to_move: Player::One,
+            move_count: 0,
+            zobrist_keys,
+            zobrist_side,
+            symmetries,
+            hashes,
+        }
+    }
+
+    /// Replaces the symbols used to draw [`Player::One`] and [`Player::Two`].
+    pub fn with_symbols(mut self, player_1: char, player_2: char) -> Self {
+        self.player_1 = player_1;
+        self.player_2 = player_2;
+        self
+    }
+
+    /// Plays by the misère rules, where the player who completes a line
+    /// loses, if `misere` is `true`.
+    pub fn with_misere(mut self, misere: bool) -> Self {
+        self.misere = misere;
+        self
+    }
+
+    /// Only counts lines of exactly `win_length` marks if `exact_length` is
+    /// `true`, so longer lines (overlines) do not end the game.
+    pub fn with_exact_length(mut self, exact_length: bool) -> Self {
+        self.exact_length = exact_length;
+        self
+    }
+
+    /// Lets `player` make the first move.
+    pub fn with_first_player(mut self, player: Player) -> Self {
+        self.side_to_move = player;
+        self
+    }
+
+    /// The symbol of the player who moves first.
+    pub fn player_1(&self) -> char {
+        self.player_1
+    }
+
+    /// The symbol of the player who moves second.
+    pub fn player_2(&self) -> char {
+        self.player_2
+    }
+
+    /// The symbol used to draw `player`.
+    pub fn symbol(&self, player: Player) -> char {
+        match player {
+            Player::One => self.player_1,
+            Player::Two => self.player_2,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// How many marks in a row are needed to win.
+    pub fn win_length(&self) -> usize {
+        self.win_length
+    }
+
+    /// Whether completing a line loses, see [`with_misere`](Self::with_misere).
+    pub fn is_misere(&self) -> bool {
+        self.misere
+    }
+
+    /// Whether only lines of exactly `win_length` marks count, see
+    /// [`with_exact_length`](Self::with_exact_length).
+    pub fn is_exact_length(&self) -> bool {
+        self.exact_length
+    }
+
+    /// Number of cells on the board.
+    pub fn size(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn cell(&self, pos: usize) -> Cell {
+        if self.stones[0].contains(pos) {
+            Cell::Occupied(Player::One)
+        } else if self.stones[1].contains(pos) {
+            Cell::Occupied(Player::Two)
+        } else {
+            Cell::Empty
+        }
+    }
+
+    /// The cells occupied by `player`.
+    pub fn stones(&self, player: Player) -> Bitboard {
+        self.stones[player.index()]
+    }
+
+    /// Every line of `win_length` cells on the board.
+    pub fn win_masks(&self) -> &[Bitboard] {
+        &self.win_masks
+    }
+
+    /// The masks of [`win_masks`](Self::win_masks) that contain `pos`.
+    pub fn masks_through(&self, pos: usize) -> impl Iterator<Item = &Bitboard> {
+        self.masks_by_cell[pos]
+            .iter()
+            .map(|&mask| &self.win_masks[mask])
+    }
+
+    /// For every mask of [`win_masks`](Self::win_masks), the cells just
+    /// before and after it on its line, if they are on the board.
+    pub fn win_mask_ends(&self) -> &[[Option<usize>; 2]] {
+        &self.mask_ends
+    }
+
+    /// Zobrist hash of the position, including the side to move.
+    pub fn hash(&self) -> u64 {
+        self.hashes[0] ^ self.side_hash()
+    }
+
+    /// The smallest [`hash`](Self::hash) over all symmetries of the board,
+    /// identical for positions that are rotations or reflections of each other.
+    pub fn canonical_hash(&self) -> u64 {
+        self.hashes.iter().min().copied().unwrap_or(0) ^ self.side_hash()
+    }
+
+    fn side_hash(&self) -> u64 {
+        match self.side_to_move {
+            Player::One => 0,
+            Player::Two => self.zobrist_side,
+        }
+    }
+
+    fn toggle_hashes(&mut self, pos: usize, player: Player) {
+        for (hash, symmetry) in self.hashes.iter_mut().zip(&self.symmetries) {
+            *hash ^= self.zobrist_keys[symmetry[pos]][player.index()];
+        }
+    }
+
+    fn occupied(&self) -> Bitboard {
+        self.stones[0] | self.stones[1]
+    }
+
+    /// Whether `player` has a line that counts. Only exact-length games have
+    /// to look at the board, and only once a line is complete.
+    fn has_line(&self, player: Player) -> bool {
+        if self.completed_lines[player.index()] == 0 {
+            return false;
+        }
+        if !self.exact_length {
+            return true;
+        }
+        let stones = &self.stones[player.index()];
+        self.win_masks
+            .iter()
+            .zip(&self.mask_ends)
+            .any(|(mask, ends)| {
+                stones.contains_all(mask) && ends.iter().flatten().all(|&end| !stones.contains(end))
+            })
+    }
+
+    /// Number of lines through `pos` that `player` has completed.
+    fn lines_through(&self, pos: usize, player: Player) -> usize {
+        let stones = &self.stones[player.index()];
+        self.masks_by_cell[pos]
+            .iter()
+            .filter(|mask| stones.contains_all(&self.win_masks[**mask]))
+            .count()
+    }
+
+    pub fn print_board(&self) {
+        println!("{}", self);
+    }
+
+    /// The player whose turn it is.
+    pub fn turn_to_move(&self) -> Player {
+        self.side_to_move
+    }
+
+    /// Hands the turn to `player` without playing a move.
+    pub fn set_turn_to_move(&mut self, player: Player) {
+        self.side_to_move = player;
+    }
+
+    /// Number of moves and passes played so far.
+    pub fn move_count(&self) -> usize {
+        self.move_count
+    }
+
+    pub(crate) fn set_move_count(&mut self, move_count: usize) {
+        self.move_count = move_count;
+    }
+
+    /// Places the mark of the side to move on `mv` and passes the turn to
+    /// the opponent. The move must be valid.
+    pub fn make_move(&mut self, mv: usize) {
+        self.place(mv, self.side_to_move);
+        self.side_to_move = self.side_to_move.opponent();
+        self.move_count += 1;
+    }
+
+    /// Clears `mv` again, taking back the move played there and giving the
+    /// turn back to the player who made it.
+    pub fn undo_move(&mut self, mv: usize) {
+        if let Cell::Occupied(player) = self.cell(mv) {
+            self.remove(mv);
+            self.side_to_move = player;
+            self.move_count = self.move_count.saturating_sub(1);
+        }
+    }
+
+    /// Skips the turn of the side to move.
+    pub fn pass(&mut self) {
+        self.side_to_move = self.side_to_move.opponent();
+        self.move_count += 1;
+    }
+
+    /// Takes back a [`pass`](Self::pass).
+    pub fn undo_pass(&mut self) {
+        self.side_to_move = self.side_to_move.opponent();
+        self.move_count = self.move_count.saturating_sub(1);
+    }
+
+    /// Puts a mark of `player` on the empty cell `pos` without touching the
+    /// side to move or the move counter, e.g. to set up handicap stones.
+    pub fn place(&mut self, pos: usize, player: Player) {
+        self.stones[player.index()].set(pos);
+        self.completed_lines[player.index()] += self.lines_through(pos, player);
+        self.toggle_hashes(pos, player);
+    }
+
+    /// Clears `pos` without touching the side to move or the move counter.
+    pub fn remove(&mut self, pos: usize) {
+        if let Cell::Occupied(player) = self.cell(pos) {
+            self.completed_lines[player.index()] -= self.lines_through(pos, player);
+            self.stones[player.index()].clear(pos);
+            self.toggle_hashes(pos, player);
+        }
+    }
+
+    /// Whether `mv` is on the board and still empty.
+    pub fn is_move_valid(&self, mv: usize) -> bool {
+        mv < self.size() && !self.occupied().contains(mv)
+    }
+
+    pub fn game_over(&self) -> GameOver {
+        let completed = if self.has_line(Player::One) {
+            Some(Player::One)
+        } else if self.has_line(Player::Two) {
+            Some(Player::Two)
+        } else {
+            None
+        };
+        if let Some(player) = completed {
+            GameOver::Winner(if self.misere {
+                player.opponent()
+            } else {
+                player
+            })
+        } else if self.occupied().count() < self.size() {
+            GameOver::OnGoing
+        } else {
+            GameOver::Draw
+        }
+    }
+
+    /// All empty cells, in index order.
+    pub fn get_all_moves(&self) -> Vec<usize> {
+        (Bitboard::full(self.size()) & !self.occupied())
+            .iter()
+            .collect()
+    }
+}
+
+impl Default for TicTacToe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for TicTacToe {
+    /// Draws the board, showing the index of every empty cell.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cell_width = (self.size() - 1).to_string().len();
+        let separator = "-".repeat(self.width * (cell_width + 3) - 3);
+        for row in 0..self.height {
+            if row > 0 {
+                writeln!(f, "{}", separator)?;
+            }
+            let cells: Vec<String> = (0..self.width)
+                .map(|col| {
+                    let pos = row * self.width + col;
+                    match self.cell(pos) {
+                        Cell::Empty => format!("{:>cell_width$}", pos),
+                        Cell::Occupied(player) => {
+                            format!("{:>cell_width$}", self.symbol(player))
+                        }
+                    }
+                })
+                .collect();
+            write!(f, "{}", cells.join(" | "))?;
+            if row + 1 < self.height {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,440 @@
+//! Gomoku: five in a row on a 15×15 board.
+//!
+//! The board is a [`TicTacToe`] of that size. By default five or more in a
+//! row win; with the [exact-length](TicTacToe::with_exact_length) rule only
+//! exactly five do, and longer lines (overlines) are ignored. Moves are
+//! written as [coordinates](crate::notation) such as `h8`, the centre.
+//!
+//! The board is far too big for the full search of the
+//! [engine](crate::engine), so the computer only considers empty cells close
+//! to the stones already played and has the [shared search](crate::search)
+//! try the most promising few of them in every position, scoring the
+//! positions at its horizon with [`TicTacToe::evaluate`].
+//!
+//! With the swap opening, the first player places three stones (their own,
+//! one of the opponent's and their own again), and the second player then
+//! picks the side they continue with. A first player who knows that
+//! the other side may be taken from them has to leave a balanced position.
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::bitboard::Bitboard;
+use crate::engine::{Difficulty, DEFAULT_TIME_BUDGET, MEDIUM_BLUNDER_PERCENT};
+use crate::game::{Cell, GameOver, Player, TicTacToe};
+use crate::rng::Rng;
+use crate::search::{self, Position, DECIDED_SCORE};
+
+/// Width and height of the board.
+pub const SIZE: usize = 15;
+
+/// Stones in a row needed to win.
+pub const WIN_LENGTH: usize = 5;
+
+/// Stones the first player places in the swap opening before the second
+/// player picks a side.
+pub const SWAP_STONES: usize = 3;
+
+/// How far from the nearest stone, in rows or columns, a move may be to be
+/// considered by the computer.
+pub const CANDIDATE_DISTANCE: usize = 2;
+
+/// How many of the most promising moves are searched in every position.
+pub const BRANCHING: usize = 10;
+
+/// How many plies the swap opening is searched to judge it: the side to
+/// move places its answer, and both sides then have the same number of
+/// stones.
+const SWAP_DEPTH: u32 = 1;
+
+#[derive(Debug, Clone)]
+pub struct Gomoku {
+    board: TicTacToe,
+    moves: Vec<usize>,
+}
+
+impl Gomoku {
+    /// An empty board where only exactly five in a row win if `exact_five`
+    /// is `true`, and five or more otherwise.
+    pub fn new(exact_five: bool) -> Self {
+        Self {
+            board: TicTacToe::with_size(SIZE, SIZE, WIN_LENGTH).with_exact_length(exact_five),
+            moves: Vec::new(),
+        }
+    }
+
+    pub fn with_first_player(mut self, player: Player) -> Self {
+        self.board = self.board.with_first_player(player);
+        self
+    }
+
+    /// Draws the stones of [`Player::One`] as `player_1` and those of
+    /// [`Player::Two`] as `player_2`.
+    pub fn with_symbols(mut self, player_1: char, player_2: char) -> Self {
+        self.board = self.board.with_symbols(player_1, player_2);
+        self
+    }
+
+    pub fn board(&self) -> &TicTacToe {
+        &self.board
+    }
+
+    pub fn turn_to_move(&self) -> Player {
+        self.board.turn_to_move()
+    }
+
+    pub fn move_count(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// The moves played so far.
+    pub fn moves(&self) -> &[usize] {
+        &self.moves
+    }
+
+    pub fn is_move_valid(&self, mv: usize) -> bool {
+        self.board.game_over() == GameOver::OnGoing && self.board.is_move_valid(mv)
+    }
+
+    /// Plays `mv` for the side to move. The move must be valid.
+    pub fn make_move(&mut self, mv: usize) {
+        self.board.make_move(mv);
+        self.moves.push(mv);
+    }
+
+    /// Takes back the last move and returns it.
+    pub fn undo_move(&mut self) -> Option<usize> {
+        let mv = self.moves.pop()?;
+        self.board.undo_move(mv);
+        Some(mv)
+    }
+
+    pub fn game_over(&self) -> GameOver {
+        self.board.game_over()
+    }
+
+    /// Parses a move typed as a coordinate such as `h8`.
+    pub fn parse_move(&self, text: &str) -> Option<usize> {
+        self.board.parse_coordinate(text)
+    }
+
+    /// The empty cells at most [`CANDIDATE_DISTANCE`] rows and columns away
+    /// from a stone, in index order, or just the centre on an empty board.
+    pub fn candidate_moves(&self) -> Vec<usize> {
+        if self.game_over() != GameOver::OnGoing {
+            return Vec::new();
+        }
+        let stones = self.board.stones(Player::One) | self.board.stones(Player::Two);
+        if stones.is_empty() {
+            return vec![SIZE / 2 * SIZE + SIZE / 2];
+        }
+        let mut near = Bitboard::EMPTY;
+        for pos in stones.iter() {
+            let (x, y) = (pos % SIZE, pos / SIZE);
+            for near_y in y.saturating_sub(CANDIDATE_DISTANCE)..=(y + CANDIDATE_DISTANCE) {
+                for near_x in x.saturating_sub(CANDIDATE_DISTANCE)..=(x + CANDIDATE_DISTANCE) {
+                    if near_x < SIZE && near_y < SIZE {
+                        near.set(near_y * SIZE + near_x);
+                    }
+                }
+            }
+        }
+        (near & !stones).iter().collect()
+    }
+
+    /// How promising `pos` looks for the side to move: every line through
+    /// it that only one player has stones on counts, more the more stones
+    /// are on it, and the mover's own lines count double.
+    fn move_priority(&self, pos: usize) -> i64 {
+        let to_move = self.turn_to_move();
+        let own = self.board.stones(to_move);
+        let other = self.board.stones(to_move.opponent());
+        self.board
+            .masks_through(pos)
+            .map(
+                |mask| match ((own & *mask).count(), (other & *mask).count()) {
+                    (count, 0) => 2 << (3 * count),
+                    (0, count) => 1 << (3 * count),
+                    _ => 0,
+                },
+            )
+            .sum()
+    }
+
+    /// [`candidate_moves`](Self::candidate_moves), most promising first.
+    fn ordered_moves(&self) -> Vec<usize> {
+        let mut moves: Vec<(usize, i64)> = self
+            .candidate_moves()
+            .into_iter()
+            .map(|mv| (mv, self.move_priority(mv)))
+            .collect();
+        moves.sort_by_key(|&(mv, priority)| (std::cmp::Reverse(priority), mv));
+        moves.into_iter().map(|(mv, _)| mv).collect()
+    }
+
+    /// Looks for the best move within `time_budget`. Only the
+    /// [`BRANCHING`] most promising [candidate moves](Self::candidate_moves)
+    /// of every position are tried, so the search reaches a useful depth on
+    /// the big board.
+    pub fn search(&mut self, time_budget: Duration) -> Option<usize> {
+        search::search(self, u32::MAX, Some(time_budget)).best_move
+    }
+
+    /// The move [`Difficulty::Perfect`] plays: the result of a
+    /// [`search`](Self::search) of [`DEFAULT_TIME_BUDGET`].
+    pub fn best_move(&mut self) -> Option<usize> {
+        self.search(DEFAULT_TIME_BUDGET)
+    }
+
+    /// A move for `difficulty` among the
+    /// [candidate moves](Self::candidate_moves): a random one, the one that
+    /// builds or blocks the most lines for [`Difficulty::Easy`], and the
+    /// [`best_move`](Self::best_move) otherwise, which
+    /// [`Difficulty::Medium`] sometimes trades for a random one.
+    pub fn computer_move(&mut self, difficulty: Difficulty, rng: &mut Rng) -> Option<usize> {
+        match difficulty {
+            Difficulty::Random => rng.choose(&self.candidate_moves()).copied(),
+            Difficulty::Easy => {
+                let moves = self.candidate_moves();
+                let best = moves.iter().map(|&mv| self.move_priority(mv)).max()?;
+                let best_moves: Vec<usize> = moves
+                    .into_iter()
+                    .filter(|&mv| self.move_priority(mv) == best)
+                    .collect();
+                rng.choose(&best_moves).copied()
+            }
+            Difficulty::Medium => {
+                if rng.chance(MEDIUM_BLUNDER_PERCENT, 100) {
+                    self.computer_move(Difficulty::Random, rng)
+                } else {
+                    self.best_move()
+                }
+            }
+            Difficulty::Perfect => self.best_move(),
+        }
+    }
+
+    /// The three stones of a swap opening for a computer playing first:
+    /// one in the centre, the opponent's next to it and another one at
+    /// most two cells from the centre, placed so that a short search favours
+    /// neither side as much as possible. Ties are broken at random.
+    pub fn swap_opening(&self, rng: &mut Rng) -> [usize; SWAP_STONES] {
+        let centre = SIZE / 2 * SIZE + SIZE / 2;
+        let around = |distance: usize| -> Vec<usize> {
+            let (x, y) = (centre % SIZE, centre / SIZE);
+            let mut cells = Vec::new();
+            for near_y in y - distance..=y + distance {
+                for near_x in x - distance..=x + distance {
+                    cells.push(near_y * SIZE + near_x);
+                }
+            }
+            cells.retain(|&cell| cell != centre);
+            cells
+        };
+        let mut openings = Vec::new();
+        for second in around(1) {
+            for third in around(2) {
+                if third == second {
+                    continue;
+                }
+                let mut gomoku = self.clone();
+                for cell in [centre, second, third] {
+                    gomoku.make_move(cell);
+                }
+                openings.push(([centre, second, third], gomoku.shallow_score().abs()));
+            }
+        }
+        let most_balanced = openings.iter().map(|&(_, score)| score).min();
+        let balanced: Vec<[usize; SWAP_STONES]> = openings
+            .iter()
+            .filter(|&&(_, score)| Some(score) == most_balanced)
+            .map(|&(opening, _)| opening)
+            .collect();
+        *rng.choose(&balanced).expect("the centre has neighbours")
+    }
+
+    /// Whether the side to move, after the swap opening, would rather take
+    /// over the other side's stones.
+    pub fn prefers_swap(&mut self) -> bool {
+        let score = self.shallow_score();
+        match self.turn_to_move() {
+            Player::One => score < 0,
+            Player::Two => score > 0,
+        }
+    }
+
+    /// The score of a [`SWAP_DEPTH`] plies deep search, from
+    /// [`Player::One`]'s point of view.
+    fn shallow_score(&mut self) -> i32 {
+        search::search(self, SWAP_DEPTH, None).score
+    }
+}
+
+impl fmt::Display for Gomoku {
+    /// Draws the board with coordinates, the last move in brackets.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let columns: Vec<String> = (0..SIZE)
+            .map(|column| {
+                self.board
+                    .coordinate(column)
+                    .trim_end_matches(char::is_numeric)
+                    .to_string()
+            })
+            .collect();
+        let header = format!("    {}", columns.join("  "));
+        writeln!(f, "{}", header)?;
+        let last = self.moves.last().copied();
+        for row in 0..SIZE {
+            let mut line = format!("{:>2} ", SIZE - row);
+            for column in 0..SIZE {
+                let pos = row * SIZE + column;
+                let symbol = match self.board.cell(pos) {
+                    Cell::Occupied(player) => self.board.symbol(player),
+                    Cell::Empty => '.',
+                };
+                if last == Some(pos) {
+                    line.push_str(&format!("[{}]", symbol));
+                } else {
+                    line.push_str(&format!(" {} ", symbol));
+                }
+            }
+            writeln!(f, "{} {}", line.trim_end(), SIZE - row)?;
+        }
+        write!(f, "{}", header)
+    }
+}
+
+impl Position for Gomoku {
+    /// Evaluating a 15×15 board is slow, so the clock is checked often.
+    const NODES_PER_TIME_CHECK: u64 = 64;
+
+    fn to_move(&self) -> Player {
+        self.turn_to_move()
+    }
+
+    fn is_over(&self) -> bool {
+        self.game_over() != GameOver::OnGoing
+    }
+
+    fn search_moves(&self) -> Vec<usize> {
+        let mut moves = self.ordered_moves();
+        moves.truncate(BRANCHING);
+        moves
+    }
+
+    fn play(&mut self, mv: usize) {
+        self.make_move(mv);
+    }
+
+    fn take_back(&mut self, _mv: usize) {
+        self.undo_move();
+    }
+
+    fn evaluate(&self) -> i32 {
+        self.board.evaluate()
+    }
+
+    /// Wins that can no longer be stopped, as found by
+    /// [`TicTacToe::evaluate`].
+    fn proven_score(&self) -> Option<i32> {
+        let score = self.board.evaluate();
+        (score.abs() >= DECIDED_SCORE).then_some(score)
+    }
+
+    fn plies_left(&self) -> u32 {
+        (SIZE * SIZE - self.board.move_count()) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(x: usize, y: usize) -> usize {
+        y * SIZE + x
+    }
+
+    /// Plays `first` and `second` alternately, starting with `first`.
+    fn play(mut gomoku: Gomoku, first: &[usize], second: &[usize]) -> Gomoku {
+        for (index, &mv) in first.iter().enumerate() {
+            gomoku.make_move(mv);
+            if let Some(&mv) = second.get(index) {
+                gomoku.make_move(mv);
+            }
+        }
+        gomoku
+    }
+
+    #[test]
+    fn overlines_only_win_without_the_exact_five_rule() {
+        // The stone in column 3 joins two lines into six in a row.
+        let row: Vec<usize> = [0, 1, 2, 4, 5, 3].map(|x| cell(x, 7)).to_vec();
+        let far: Vec<usize> = [0, 2, 4, 6, 8].map(|x| cell(x, 0)).to_vec();
+        let exact = play(Gomoku::new(true), &row, &far);
+        assert_eq!(exact.game_over(), GameOver::OnGoing);
+        let free = play(Gomoku::new(false), &row, &far);
+        assert_eq!(free.game_over(), GameOver::Winner(Player::One));
+
+        let five: Vec<usize> = (0..5).map(|x| cell(x, 7)).collect();
+        let exact = play(Gomoku::new(true), &five, &far);
+        assert_eq!(exact.game_over(), GameOver::Winner(Player::One));
+    }
+
+    #[test]
+    fn candidate_moves_stay_on_the_board() {
+        let gomoku = Gomoku::new(false);
+        assert_eq!(gomoku.candidate_moves(), vec![cell(7, 7)]);
+
+        for corner in [cell(0, 0), cell(SIZE - 1, SIZE - 1)] {
+            let gomoku = play(Gomoku::new(false), &[corner], &[]);
+            let moves = gomoku.candidate_moves();
+            assert_eq!(moves.len(), 8, "{:?}", moves);
+            let (x, y) = (corner % SIZE, corner / SIZE);
+            for mv in moves {
+                assert!((mv % SIZE).abs_diff(x) <= CANDIDATE_DISTANCE);
+                assert!((mv / SIZE).abs_diff(y) <= CANDIDATE_DISTANCE);
+            }
+        }
+
+        // A stone on the left edge must not bring in cells on the right
+        // edge of the row above.
+        let gomoku = play(Gomoku::new(false), &[cell(0, 7)], &[]);
+        let moves = gomoku.candidate_moves();
+        assert_eq!(moves.len(), 14);
+        assert!(moves.iter().all(|&mv| mv % SIZE <= CANDIDATE_DISTANCE));
+    }
+
+    #[test]
+    fn opens_around_the_centre() {
+        let gomoku = Gomoku::new(false);
+        let opening = gomoku.swap_opening(&mut Rng::new(3));
+        assert_eq!(opening, gomoku.swap_opening(&mut Rng::new(3)));
+        let centre = cell(7, 7);
+        assert_eq!(opening[0], centre);
+        let distance = |mv: usize| (mv % SIZE).abs_diff(7).max((mv / SIZE).abs_diff(7));
+        assert_eq!(distance(opening[1]), 1);
+        assert!((1..=2).contains(&distance(opening[2])));
+        assert_ne!(opening[1], opening[2]);
+
+        let gomoku = play(gomoku, &[opening[0], opening[2]], &[opening[1]]);
+        assert_eq!(gomoku.turn_to_move(), Player::Two);
+        assert_eq!(gomoku.game_over(), GameOver::OnGoing);
+    }
+
+    #[test]
+    fn swaps_to_the_side_that_is_ahead() {
+        let far: Vec<usize> = [0, 2, 4, 6].map(|x| cell(x, 0)).to_vec();
+        let four: Vec<usize> = (5..9).map(|x| cell(x, 7)).collect();
+
+        // O to move against an open four of X's.
+        let mut gomoku = play(Gomoku::new(false), &four, &far[..3]);
+        assert!(gomoku.prefers_swap());
+        // X to move against an open four of O's.
+        let mut gomoku = play(Gomoku::new(false), &far, &four);
+        assert!(gomoku.prefers_swap());
+        // O to move with an open three of its own.
+        let mut gomoku = play(Gomoku::new(false), &far, &four[..3]);
+        assert_eq!(gomoku.turn_to_move(), Player::Two);
+        assert!(!gomoku.prefers_swap());
+    }
+}
@@ -0,0 +1,99 @@
+//! Move history with undo and redo.
+
+use crate::game::TicTacToe;
+
+/// The moves played in a game, plus the moves taken back that can still be
+/// replayed. Playing a new move forgets the moves that were taken back.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    played: Vec<usize>,
+    undone: Vec<usize>,
+}
+
+impl History {
+    /// A history of `moves` that were already played.
+    pub fn from_moves(moves: Vec<usize>) -> Self {
+        Self {
+            played: moves,
+            undone: Vec::new(),
+        }
+    }
+
+    /// The moves played so far, oldest first.
+    pub fn moves(&self) -> &[usize] {
+        &self.played
+    }
+
+    /// Plays `mv` on `tictactoe` and remembers it.
+    pub fn play(&mut self, tictactoe: &mut TicTacToe, mv: usize) {
+        tictactoe.make_move(mv);
+        self.played.push(mv);
+        self.undone.clear();
+    }
+
+    /// Takes back the last move from `tictactoe`, or returns `None` if no
+    /// move was played.
+    pub fn undo(&mut self, tictactoe: &mut TicTacToe) -> Option<usize> {
+        let mv = self.played.pop()?;
+        tictactoe.undo_move(mv);
+        self.undone.push(mv);
+        Some(mv)
+    }
+
+    /// Replays the last move taken back, or returns `None` if there is none.
+    pub fn redo(&mut self, tictactoe: &mut TicTacToe) -> Option<usize> {
+        let mv = self.undone.pop()?;
+        tictactoe.make_move(mv);
+        self.played.push(mv);
+        Some(mv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Player;
+
+    #[test]
+    fn undoes_and_redoes_moves() {
+        let mut tictactoe = TicTacToe::new();
+        let mut history = History::default();
+        history.play(&mut tictactoe, 4);
+        history.play(&mut tictactoe, 0);
+
+        assert_eq!(history.undo(&mut tictactoe), Some(0));
+        assert_eq!(history.undo(&mut tictactoe), Some(4));
+        assert_eq!(history.undo(&mut tictactoe), None);
+        assert_eq!(tictactoe.get_all_moves().len(), 9);
+
+        assert_eq!(history.redo(&mut tictactoe), Some(4));
+        assert_eq!(history.moves(), [4]);
+        assert_eq!(tictactoe.move_count(), 1);
+    }
+
+    #[test]
+    fn a_new_move_clears_the_redo_stack() {
+        let mut tictactoe = TicTacToe::new();
+        let mut history = History::from_moves(Vec::new());
+        history.play(&mut tictactoe, 4);
+        history.play(&mut tictactoe, 0);
+        history.undo(&mut tictactoe);
+        history.play(&mut tictactoe, 8);
+
+        assert_eq!(history.redo(&mut tictactoe), None);
+        assert_eq!(history.moves(), [4, 8]);
+        assert!(tictactoe.is_move_valid(0));
+    }
+
+    #[test]
+    fn continues_from_played_moves() {
+        let mut tictactoe = TicTacToe::new();
+        for mv in [4, 0] {
+            tictactoe.make_move(mv);
+        }
+        let mut history = History::from_moves(vec![4, 0]);
+        assert_eq!(history.undo(&mut tictactoe), Some(0));
+        assert_eq!(history.moves(), [4]);
+        assert_eq!(tictactoe.turn_to_move(), Player::Two);
+    }
+}
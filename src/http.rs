@@ -0,0 +1,338 @@
+//! A small HTTP/1.1 server side: reads requests and writes responses, one
+//! request per connection.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+use crate::json::Json;
+
+/// Largest request body that is read.
+const MAX_BODY: usize = 64 * 1024;
+
+/// Most header lines a request may have.
+const MAX_HEADERS: usize = 100;
+
+/// Longest request line or header line, in bytes.
+const MAX_LINE: usize = 8 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// The path without the query string, percent-decoded.
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    /// Header names are lowercase.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    /// Reads one request, or `None` if the connection closed before it
+    /// started. A request line or header that is too long, or too many
+    /// headers, fail with [`HeadersTooLarge`]; see [`error_status`].
+    pub fn read(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+        let mut line = String::new();
+        if read_line(reader, &mut line)? == 0 {
+            return Ok(None);
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Err(invalid("invalid request line"));
+        };
+        let method = method.to_string();
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let path = percent_decode(path);
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(name), percent_decode(value))
+            })
+            .collect();
+
+        let mut headers = Vec::new();
+        loop {
+            line.clear();
+            if read_line(reader, &mut line)? == 0 {
+                return Err(invalid("connection closed in the headers"));
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, HeadersTooLarge));
+            }
+            let (name, value) = header
+                .split_once(':')
+                .ok_or_else(|| invalid("invalid header"))?;
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+
+        let mut request = Request {
+            method,
+            path,
+            query,
+            headers,
+            body: String::new(),
+        };
+        let length = match request.header("content-length") {
+            Some(length) => length
+                .parse()
+                .map_err(|_| invalid("invalid content length"))?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err(invalid("request body too large"));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        request.body = String::from_utf8(body).map_err(|_| invalid("body is not UTF-8"))?;
+        Ok(Some(request))
+    }
+
+    /// The value of header `name`, which must be lowercase.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The value of query parameter `name`.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(parameter, _)| parameter == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The request line or headers of a request are larger than this server
+/// reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeadersTooLarge;
+
+impl fmt::Display for HeadersTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "request headers too large")
+    }
+}
+
+impl Error for HeadersTooLarge {}
+
+/// The status to answer a request that [`Request::read`] failed on: 431 if
+/// its headers were too large, 400 otherwise.
+pub fn error_status(err: &io::Error) -> u16 {
+    match err.get_ref() {
+        Some(inner) if inner.is::<HeadersTooLarge>() => 431,
+        _ => 400,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(status: u16, body: &Json) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: body.to_string(),
+        }
+    }
+
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into(),
+        }
+    }
+
+    /// A JSON `{"error": message}` response.
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::json(
+            status,
+            &Json::object([("error", Json::String(message.into()))]),
+        )
+    }
+
+    /// Writes the response and asks the client to close the connection.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\n\
+             Content-Type: {}\r\n\
+             Content-Length: {}\r\n\
+             Access-Control-Allow-Origin: *\r\n\
+             Connection: close\r\n\r\n{}",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len(),
+            self.body
+        )?;
+        writer.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        431 => "Request Header Fields Too Large",
+        _ => "Unknown",
+    }
+}
+
+/// Decodes `%XX` escapes and `+` for spaces. Invalid escapes are kept as
+/// they are.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Reads a line like [`BufRead::read_line`], but fails with
+/// [`HeadersTooLarge`] instead of reading more than [`MAX_LINE`] bytes.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<usize> {
+    let read = reader.take(MAX_LINE as u64 + 1).read_line(line)?;
+    if read > MAX_LINE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, HeadersTooLarge));
+    }
+    Ok(read)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(text: &str) -> io::Result<Option<Request>> {
+        Request::read(&mut text.as_bytes())
+    }
+
+    #[test]
+    fn reads_requests() {
+        let request = read(
+            "POST /games/1%2F2/moves?time_ms=500&name=a+b%21&flag HTTP/1.1\r\n\
+             Host: localhost\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: 12\r\n\
+             \r\n\
+             {\"cell\": 4}\n",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/games/1/2/moves");
+        assert_eq!(request.query("time_ms"), Some("500"));
+        assert_eq!(request.query("name"), Some("a b!"));
+        assert_eq!(request.query("flag"), Some(""));
+        assert_eq!(request.query("missing"), None);
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.header("host"), Some("localhost"));
+        assert_eq!(request.body, "{\"cell\": 4}\n");
+    }
+
+    #[test]
+    fn reads_nothing_from_a_closed_connection() {
+        assert!(read("").unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_request_lines() {
+        for text in ["\r\n\r\n", "GET\r\n\r\n", "   \r\n"] {
+            let err = read(text).unwrap_err();
+            assert_eq!(error_status(&err), 400, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        for text in [
+            "GET / HTTP/1.1\r\nno colon\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: localhost\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: lots\r\n\r\n",
+            "POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort",
+            "POST / HTTP/1.1\r\nContent-Length: 999999999\r\n\r\n",
+        ] {
+            let err = read(text).unwrap_err();
+            assert_eq!(error_status(&err), 400, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_headers() {
+        let long_path = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        let long_header = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_LINE));
+        let many_headers = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X: y\r\n".repeat(MAX_HEADERS + 1)
+        );
+        for text in [long_path, long_header, many_headers] {
+            let err = read(&text).unwrap_err();
+            assert_eq!(error_status(&err), 431);
+        }
+        let most_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "X: y\r\n".repeat(MAX_HEADERS));
+        assert!(read(&most_headers).unwrap().is_some());
+    }
+
+    #[test]
+    fn writes_responses() {
+        let mut written = Vec::new();
+        Response::error(404, "no such game")
+            .write_to(&mut written)
+            .unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(written.contains("Content-Type: application/json\r\n"));
+        assert!(written.contains("Content-Length: 24\r\n"));
+        assert!(written.ends_with("\r\n\r\n{\"error\":\"no such game\"}"));
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode("a%20b+c"), "a b c");
+        assert_eq!(percent_decode("%C3%A9"), "é");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+    }
+}
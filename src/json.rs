@@ -0,0 +1,438 @@
+//! Just enough JSON for the [HTTP API](crate::api): a value type that
+//! prints itself and a parser for request bodies.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+/// A JSON value. Objects keep their keys in insertion order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// An object with the given members.
+    pub fn object<const N: usize>(members: [(&str, Json); N]) -> Json {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// The member `key` of an object, or `None` for other values.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The value as a whole number that is not negative.
+    pub fn as_usize(&self) -> Option<usize> {
+        match *self {
+            Json::Number(n) if n >= 0.0 && n.fract() == 0.0 && n <= usize::MAX as f64 => {
+                Some(n as usize)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<i32> for Json {
+    fn from(value: i32) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(values: Vec<T>) -> Self {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(n) if n.is_finite() => write!(f, "{}", n),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(text) => write_string(f, text),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Why a text is not valid JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseJsonError(String);
+
+impl fmt::Display for ParseJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid JSON: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseJsonError {}
+
+impl FromStr for Json {
+    type Err = ParseJsonError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut chars = text.chars().peekable();
+        let value = parse_value(&mut chars, 0)?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(value),
+            Some(c) => Err(ParseJsonError(format!(
+                "unexpected '{}' after the value",
+                c
+            ))),
+        }
+    }
+}
+
+/// How deeply arrays and objects may nest, so a hostile request body cannot
+/// overflow the stack of the recursive parser.
+pub const MAX_DEPTH: usize = 64;
+
+type Input<'a> = Peekable<Chars<'a>>;
+
+fn skip_whitespace(chars: &mut Input) {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+}
+
+fn expect(chars: &mut Input, expected: char) -> Result<(), ParseJsonError> {
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        Some(c) => Err(ParseJsonError(format!(
+            "expected '{}', found '{}'",
+            expected, c
+        ))),
+        None => Err(ParseJsonError(format!(
+            "expected '{}', found the end",
+            expected
+        ))),
+    }
+}
+
+/// Parses a value nested in `depth` arrays and objects.
+fn parse_value(chars: &mut Input, depth: usize) -> Result<Json, ParseJsonError> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('{' | '[') if depth == MAX_DEPTH => Err(ParseJsonError(format!(
+            "nested deeper than {} levels",
+            MAX_DEPTH
+        ))),
+        Some('{') => parse_object(chars, depth + 1),
+        Some('[') => parse_array(chars, depth + 1),
+        Some('"') => parse_string(chars).map(Json::String),
+        Some('t') => parse_literal(chars, "true", Json::Bool(true)),
+        Some('f') => parse_literal(chars, "false", Json::Bool(false)),
+        Some('n') => parse_literal(chars, "null", Json::Null),
+        Some(c) if *c == '-' || c.is_ascii_digit() => parse_number(chars),
+        Some(c) => Err(ParseJsonError(format!("unexpected '{}'", c))),
+        None => Err(ParseJsonError("unexpected end".to_string())),
+    }
+}
+
+fn parse_literal(chars: &mut Input, literal: &str, value: Json) -> Result<Json, ParseJsonError> {
+    for expected in literal.chars() {
+        expect(chars, expected)?;
+    }
+    Ok(value)
+}
+
+fn parse_number(chars: &mut Input) -> Result<Json, ParseJsonError> {
+    let mut text = String::new();
+    while let Some(c) = chars.next_if(|c| matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9')) {
+        text.push(c);
+    }
+    text.parse()
+        .map(Json::Number)
+        .map_err(|_| ParseJsonError(format!("invalid number '{}'", text)))
+}
+
+fn parse_string(chars: &mut Input) -> Result<String, ParseJsonError> {
+    expect(chars, '"')?;
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(text),
+            Some('\\') => text.push(match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| ParseJsonError(format!("invalid escape '\\u{}'", hex)))?
+                }
+                Some(c @ ('"' | '\\' | '/')) => c,
+                _ => return Err(ParseJsonError("invalid escape".to_string())),
+            }),
+            Some(c) => text.push(c),
+            None => return Err(ParseJsonError("unterminated string".to_string())),
+        }
+    }
+}
+
+fn parse_array(chars: &mut Input, depth: usize) -> Result<Json, ParseJsonError> {
+    expect(chars, '[')?;
+    let mut values = Vec::new();
+    skip_whitespace(chars);
+    if chars.next_if_eq(&']').is_some() {
+        return Ok(Json::Array(values));
+    }
+    loop {
+        values.push(parse_value(chars, depth)?);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => {}
+            Some(']') => return Ok(Json::Array(values)),
+            _ => return Err(ParseJsonError("expected ',' or ']'".to_string())),
+        }
+    }
+}
+
+fn parse_object(chars: &mut Input, depth: usize) -> Result<Json, ParseJsonError> {
+    expect(chars, '{')?;
+    let mut members = Vec::new();
+    skip_whitespace(chars);
+    if chars.next_if_eq(&'}').is_some() {
+        return Ok(Json::Object(members));
+    }
+    loop {
+        skip_whitespace(chars);
+        let key = parse_string(chars)?;
+        skip_whitespace(chars);
+        expect(chars, ':')?;
+        members.push((key, parse_value(chars, depth)?));
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => {}
+            Some('}') => return Ok(Json::Object(members)),
+            _ => return Err(ParseJsonError("expected ',' or '}'".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Json, ParseJsonError> {
+        text.parse()
+    }
+
+    #[test]
+    fn parses_values() {
+        assert_eq!(parse("null"), Ok(Json::Null));
+        assert_eq!(parse(" true "), Ok(Json::Bool(true)));
+        assert_eq!(parse("false"), Ok(Json::Bool(false)));
+        assert_eq!(
+            parse(r#"{"cells": [1, 2], "name": "x", "empty": {}, "none": []}"#),
+            Ok(Json::object([
+                ("cells", vec![1usize, 2].into()),
+                ("name", "x".into()),
+                ("empty", Json::Object(Vec::new())),
+                ("none", Json::Array(Vec::new())),
+            ]))
+        );
+    }
+
+    #[test]
+    fn parses_numbers() {
+        for (text, value) in [
+            ("0", 0.0),
+            ("42", 42.0),
+            ("-7", -7.0),
+            ("2.5", 2.5),
+            ("1e3", 1000.0),
+            ("-1.5E-2", -0.015),
+        ] {
+            assert_eq!(parse(text), Ok(Json::Number(value)), "{}", text);
+        }
+        for text in ["-", "1.2.3", "1e", "--1"] {
+            assert!(parse(text).is_err(), "{}", text);
+        }
+        assert_eq!(parse("3").unwrap().as_usize(), Some(3));
+        assert_eq!(parse("3.5").unwrap().as_usize(), None);
+        assert_eq!(parse("-3").unwrap().as_usize(), None);
+    }
+
+    #[test]
+    fn parses_escapes() {
+        assert_eq!(
+            parse(r#""a\"b\\c\/d\n\r\t\b\féA""#),
+            Ok(Json::String("a\"b\\c/d\n\r\t\u{8}\u{c}éA".to_string()))
+        );
+        for text in [r#""\x""#, r#""\u12""#, r#""\uzzzz""#, r#""\ud800""#] {
+            assert!(parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn round_trips_strings() {
+        let text = "quote \" backslash \\ newline \n tab \t bell \u{7} é";
+        let json = Json::String(text.to_string());
+        assert_eq!(parse(&json.to_string()), Ok(json));
+    }
+
+    #[test]
+    fn prints_values() {
+        let json = Json::object([
+            ("a", Json::Null),
+            ("b", vec![true, false].into()),
+            ("c", Json::Number(1.5)),
+            ("d", Json::Number(f64::NAN)),
+        ]);
+        assert_eq!(
+            json.to_string(),
+            r#"{"a":null,"b":[true,false],"c":1.5,"d":null}"#
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        for text in [
+            "",
+            "   ",
+            "[",
+            "[1,",
+            "[1",
+            "{",
+            r#"{"a""#,
+            r#"{"a":"#,
+            r#"{"a":1"#,
+            r#"{"a":1,"#,
+            r#""abc"#,
+            r#""\u00"#,
+            "tru",
+            "nul",
+        ] {
+            assert!(parse(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for text in [
+            "[1 2]",
+            "[1,]",
+            r#"{"a" 1}"#,
+            "{a:1}",
+            r#"{"a":1,}"#,
+            "1 2",
+            "True",
+            "'a'",
+        ] {
+            assert!(parse(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+        let objects = r#"{"a":"#.repeat(MAX_DEPTH + 1) + "1" + &"}".repeat(MAX_DEPTH + 1);
+        assert!(parse(&objects).is_err());
+        // Far deeper input fails cleanly instead of overflowing the stack.
+        assert!(parse(&"[".repeat(1_000_000)).is_err());
+    }
+}
@@ -0,0 +1,28 @@
+//! Tic-tac-toe on arbitrary N×N boards with a K-in-a-row win condition,
+//! plus a minimax engine that plays it.
+//!
+//! [`TicTacToe`] holds the game state and rules, the engine methods
+//! ([`TicTacToe::minimax`], [`TicTacToe::best_move`]) live in [`engine`].
+
+pub mod analysis;
+pub mod api;
+pub mod bitboard;
+pub mod engine;
+pub mod game;
+pub mod gomoku;
+pub mod history;
+pub mod http;
+pub mod json;
+pub mod net;
+pub mod notation;
+pub mod protocol;
+pub mod record;
+pub mod rng;
+pub mod search;
+pub mod tournament;
+pub mod transposition;
+pub mod ultimate;
+pub mod websocket;
+
+pub use engine::{Controller, Difficulty};
+pub use game::{Cell, GameOver, Player, TicTacToe};
@@ -1,13 +1,39 @@
+mod cli;
+
 use std::io::{self, Write};
-use std::process::Command;
+use std::net::TcpListener;
+use std::sync::Arc;
+
+use cli::{Command, ContestantSpec, GameOptions, MatchOptions, Network, Variant};
+use tic_tac_toe::analysis::{self, analyze_game, MoveQuality};
+use tic_tac_toe::api::{Api, PublishedGame};
+use tic_tac_toe::bitboard::MAX_CELLS;
+use tic_tac_toe::engine::DEFAULT_TIME_BUDGET;
+use tic_tac_toe::game::check_board;
+use tic_tac_toe::gomoku::{Gomoku, SWAP_STONES};
+use tic_tac_toe::history::History;
+use tic_tac_toe::net::Connection;
+use tic_tac_toe::protocol::{self, ExternalEngine};
+use tic_tac_toe::record::GameRecord;
+use tic_tac_toe::rng::Rng;
+use tic_tac_toe::tournament::{play_match, Contestant};
+use tic_tac_toe::ultimate::{Ultimate, BOARDS};
+use tic_tac_toe::{Controller, Difficulty, GameOver, Player, TicTacToe};
+
+/// How long the board stays on screen after a computer move when nobody
+/// has to type anything, so computer vs computer games can be followed.
+const DEMO_MOVE_DELAY: std::time::Duration = std::time::Duration::from_millis(700);
+
+/// How long the post-game analysis may think about each position.
+const ANALYSIS_TIME_PER_MOVE: std::time::Duration = std::time::Duration::from_millis(500);
 
 fn clear_terminal() {
     #[cfg(target_os = "windows")]
     {
-        Command::new("cmd")
-            .args(&["/C", "cls"])
+        std::process::Command::new("cmd")
+            .args(["/C", "cls"])
             .status()
-            .except("error")
+            .expect("error");
     }
 
     #[cfg(not(target_os = "windows"))]
@@ -17,247 +43,952 @@ fn clear_terminal() {
     }
 }
 
-enum GameOver {
-    Winner(char),
-    Draw,
-    OnGoing,
-}
-
-#[derive(Debug)]
-struct TicTacToe {
-    player_1: char,
-    player_2: char,
-    board: Vec<char>,
+fn main() {
+    match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Menu) => menu(),
+        Ok(Command::Play(options)) => play(*options),
+        Ok(Command::Replay(path)) => replay(&load_game(&path)),
+        Ok(Command::Analyze(path)) => print_analysis(&load_game(&path)),
+        Ok(Command::Engine) => {
+            if let Err(err) = protocol::run_engine(io::stdin().lock(), io::stdout()) {
+                eprintln!("error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Ok(Command::Match(options)) => run_match(*options),
+        Ok(Command::Serve(address)) => serve(&address),
+        Ok(Command::Help) => println!("{}", cli::USAGE),
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    }
 }
 
-impl TicTacToe {
-    fn new() -> Self {
-        let player_1 = 'X';
-        let player_2 = 'O';
-        let board = vec!['0', '1', '2', '3', '4', '5', '6', '7', '8'];
-        Self {
-            player_1,
-            player_2,
-            board,
+fn play(options: GameOptions) {
+    let mut rng = options.seed.map_or_else(Rng::from_entropy, Rng::new);
+    if options.variant == Variant::Ultimate {
+        let first = options.first.unwrap_or_else(|| Player::random(&mut rng));
+        let ultimate = Ultimate::new()
+            .with_symbols(options.symbols.0, options.symbols.1)
+            .with_first_player(first);
+        play_ultimate(ultimate, options.controllers, rng);
+        return;
+    }
+    if options.variant == Variant::Gomoku {
+        let first = options.first.unwrap_or_else(|| Player::random(&mut rng));
+        let gomoku = Gomoku::new(options.exact_five)
+            .with_symbols(options.symbols.0, options.symbols.1)
+            .with_first_player(first);
+        play_gomoku(gomoku, options.controllers, options.swap, rng);
+        return;
+    }
+    let mut seats = Seats::new(options.controllers);
+    seats.engines = options
+        .engines
+        .map(|command| command.map(|command| spawn_engine(&command)));
+    let mut record = match (&options.network, &options.load) {
+        (Some(network @ (Network::Join(address) | Network::Server(address, _))), _) => {
+            let request = match network {
+                Network::Server(_, request) => Some(request),
+                _ => None,
+            };
+            println!("Joining the game at {}...", address);
+            let (connection, start, side) = Connection::join(address.as_str(), request, |lobby| {
+                println!("Opened lobby {}, waiting for someone to join it...", lobby)
+            })
+            .unwrap_or_else(|err| network_error(address, err));
+            seats.remote = Some(connection);
+            seats.controllers = [Controller::Remote; 2];
+            seats.controllers[side.index()] = Controller::Human;
+            GameRecord::new(&start)
+        }
+        (_, Some(path)) => load_game(path),
+        (_, None) => {
+            let start = match options.position {
+                Some(position) => position,
+                None => {
+                    let first = options.first.unwrap_or_else(|| Player::random(&mut rng));
+                    TicTacToe::with_size(options.width, options.height, options.win_length)
+                        .with_misere(options.variant == Variant::Misere)
+                        .with_first_player(first)
+                }
+            };
+            GameRecord::new(&start)
         }
+    };
+    record.set_symbols(options.symbols.0, options.symbols.1);
+    if let Some(Network::Host(address)) = &options.network {
+        let guest = if options.controllers[0] == Controller::Remote {
+            Player::One
+        } else {
+            Player::Two
+        };
+        let listener =
+            TcpListener::bind(address.as_str()).unwrap_or_else(|err| network_error(address, err));
+        println!("Waiting for the other player on {}...", address);
+        let current = record
+            .replay()
+            .expect("records are checked when they are created");
+        let connection = Connection::host(&listener, &current, guest)
+            .unwrap_or_else(|err| network_error(address, err));
+        seats.remote = Some(connection);
+        // The guest only knows the current position, so both records start there.
+        record = GameRecord::new(&current);
+    }
+    if let Some(address) = &options.watch {
+        let listener =
+            TcpListener::bind(address.as_str()).unwrap_or_else(|err| network_error(address, err));
+        let api = Arc::new(Api::new());
+        let current = record
+            .replay()
+            .expect("records are checked when they are created");
+        let published = api.publish(&current);
+        println!(
+            "Spectators can watch on ws://{}/games/{}/watch",
+            address,
+            published.id()
+        );
+        let server = Arc::clone(&api);
+        std::thread::spawn(move || server.serve(listener));
+        seats.published = Some(published);
     }
 
-    fn print_board(&self) {
-        for i in 0..3 {
-            if i > 0 {
-                println!("---------");
-            }
+    let controllers = seats.controllers;
+    let record = start_game(&mut seats, record, rng);
+    let human = controllers.contains(&Controller::Human);
+    if options.analyze {
+        print_analysis(&record);
+    } else if human {
+        ask_analysis(&record);
+    }
+    match &options.save {
+        Some(path) => save_game(&record, path),
+        None if human => ask_save_game(&record),
+        None => {}
+    }
+}
+
+fn run_match(options: MatchOptions) {
+    let mut rng = options.seed.map_or_else(Rng::from_entropy, Rng::new);
+    let mut contestants = options.contestants.map(|spec| match spec {
+        ContestantSpec::Computer(level) => Contestant::Computer(level),
+        ContestantSpec::Engine(command) => Contestant::Engine {
+            engine: spawn_engine(&command),
+            movetime: options.movetime,
+        },
+    });
+    let start = TicTacToe::with_size(options.width, options.height, options.win_length);
+    println!(
+        "{} vs {}, {} games",
+        contestants[0].name(),
+        contestants[1].name(),
+        options.games
+    );
+    let result = play_match(
+        &mut contestants,
+        &start,
+        options.games,
+        &mut rng,
+        |record, so_far| {
             println!(
-                "{} | {} | {}",
-                self.board[3 * i],
-                self.board[3 * i + 1],
-                self.board[3 * i + 2]
+                "Game {:>4}: {} (+{} ={} -{})",
+                so_far.games(),
+                record.result(),
+                so_far.wins,
+                so_far.draws,
+                so_far.losses
             );
+        },
+    );
+    match result {
+        Ok(result) => println!("\nResults for {}:\n{}", contestants[0].name(), result),
+        Err(err) => {
+            eprintln!("error: the match was aborted: {}", err);
+            std::process::exit(1);
         }
     }
+}
 
-    fn turn_to_move(&self) -> char {
-        let total_instences_player_1: u8 = self
-            .board
-            .iter()
-            .map(|pos| if pos == &self.player_1 { 1 } else { 0 })
-            .sum();
-        let total_instences_player_2: u8 = self
-            .board
-            .iter()
-            .map(|pos| if pos == &self.player_2 { 1 } else { 0 })
-            .sum();
-        if total_instences_player_1 <= total_instences_player_2 {
-            self.player_1
-        } else {
-            self.player_2
+fn serve(address: &str) {
+    let listener = TcpListener::bind(address).unwrap_or_else(|err| network_error(address, err));
+    println!("Serving the HTTP API on http://{}", address);
+    Arc::new(Api::new()).serve(listener);
+}
+
+fn menu() {
+    clear_terminal();
+    println!("Welcome to the Simpel TicTacToe game");
+    loop {
+        println!("1. Human vs Human\n2. Human vs Computer\n3. Computer vs Computer\n4. Exit");
+        let user_input = input("Pick an option (1, 2, 3, 4): ");
+        match user_input.as_str() {
+            "1" => {
+                let first = ask_first_player();
+                menu_game([Controller::Human; 2], first)
+            }
+            "2" => {
+                let computer = Controller::Computer(ask_difficulty("the computer"));
+                let controllers = match ask_side() {
+                    Player::One => [Controller::Human, computer],
+                    Player::Two => [computer, Controller::Human],
+                };
+                let first = ask_first_player();
+                menu_game(controllers, first)
+            }
+            "3" => {
+                let controllers = [
+                    Controller::Computer(ask_difficulty("X")),
+                    Controller::Computer(ask_difficulty("O")),
+                ];
+                let first = ask_first_player();
+                menu_game(controllers, first)
+            }
+            "4" => break,
+            _ => println!("Invalid option, please pick a valid option."),
         }
     }
 
-    fn make_move(&mut self, mv: usize) {
-        let turn = self.turn_to_move();
-        self.board[mv] = turn;
-    }
+    println!("Thanks for playing, cya")
+}
 
-    fn undo_move(&mut self, mv: usize) {
-        self.board[mv] = std::char::from_digit(mv as u32, 10).unwrap();
+fn ask_variant() -> Variant {
+    loop {
+        println!(
+            "1. Classic\n2. Misère (completing a line loses)\n3. Ultimate (nine boards)\n\
+             4. Gomoku (five in a row on 15x15)"
+        );
+        match input("Which game (1, 2, 3, 4): ").as_str() {
+            "1" => return Variant::Classic,
+            "2" => return Variant::Misere,
+            "3" => return Variant::Ultimate,
+            "4" => return Variant::Gomoku,
+            _ => println!("Invalid option, please pick a valid option."),
+        }
     }
+}
 
-    fn is_move_valid(&self, mv: usize) -> bool {
-        if mv >= self.board.len() {
-            return false;
+fn ask_board_size() -> TicTacToe {
+    loop {
+        let user_input = input("Board width, height and win length (leave empty for 3 3 3): ");
+        if user_input.is_empty() {
+            return TicTacToe::new();
         }
-
-        if self.board[mv] != self.player_1 && self.board[mv] != self.player_2 {
-            true
-        } else {
-            false
+        let numbers: Option<Vec<usize>> = user_input
+            .split_whitespace()
+            .map(|n| n.parse().ok())
+            .collect();
+        match numbers.as_deref() {
+            Some(&[width, height, win_length])
+                if check_board(width, height, win_length).is_ok() =>
+            {
+                return TicTacToe::with_size(width, height, win_length)
+            }
+            _ => println!(
+                "Invalid board size, please enter three positive numbers (at most {} cells).",
+                MAX_CELLS
+            ),
         }
     }
+}
 
-    fn game_over(&self) -> GameOver {
-        let winning_positions = [
-            [0, 1, 2],
-            [3, 4, 5],
-            [6, 7, 8],
-            [0, 3, 6],
-            [1, 4, 7],
-            [2, 5, 8],
-            [0, 4, 8],
-            [2, 4, 6],
-        ];
-        for player in [self.player_1, self.player_2] {
-            for winning_position in winning_positions {
-                if player == self.board[winning_position[0]]
-                    && self.board[winning_position[0]] == self.board[winning_position[1]]
-                    && self.board[winning_position[1]] == self.board[winning_position[2]]
-                {
-                    return GameOver::Winner(player);
-                };
-            }
+fn ask_difficulty(who: &str) -> Difficulty {
+    loop {
+        println!("1. Random\n2. Easy\n3. Medium\n4. Perfect");
+        match input(&format!("Pick a difficulty for {} (1, 2, 3, 4): ", who)).as_str() {
+            "1" => return Difficulty::Random,
+            "2" => return Difficulty::Easy,
+            "3" => return Difficulty::Medium,
+            "4" => return Difficulty::Perfect,
+            _ => println!("Invalid option, please pick a valid option."),
         }
+    }
+}
 
-        for pos in self.board.iter() {
-            if *pos != self.player_1 && *pos != self.player_2 {
-                return GameOver::OnGoing;
-            }
+fn ask_side() -> Player {
+    loop {
+        println!("1. X\n2. O");
+        match input("Which side do you want to play (1, 2): ").as_str() {
+            "1" => return Player::One,
+            "2" => return Player::Two,
+            _ => println!("Invalid option, please pick a valid option."),
         }
-        GameOver::Draw
     }
+}
 
-    fn evaluate(&self) -> i8 {
-        match self.game_over() {
-            GameOver::Draw => 0,
-            GameOver::Winner(player) => {
-                if player == self.player_1 {
-                    1
-                } else {
-                    -1
-                }
-            }
-            GameOver::OnGoing => panic!("why the hell did you call me when the game was on going?"),
+/// Returns `None` when the first player should be picked at random.
+fn ask_first_player() -> Option<Player> {
+    loop {
+        println!("1. X\n2. O\n3. Random");
+        match input("Who moves first (1, 2, 3): ").as_str() {
+            "1" => return Some(Player::One),
+            "2" => return Some(Player::Two),
+            "3" => return None,
+            _ => println!("Invalid option, please pick a valid option."),
         }
     }
+}
 
-    fn get_all_moves(&self) -> Vec<usize> {
-        (0..self.board.len())
-            .filter(|pos| self.board[*pos] != self.player_1 && self.board[*pos] != self.player_2)
-            .collect()
-    }
+/// Plays a game from the menu and offers to save it.
+fn new_game(controllers: [Controller; 2], tictactoe: TicTacToe, first: Option<Player>) {
+    let mut rng = Rng::from_entropy();
+    let first = first.unwrap_or_else(|| Player::random(&mut rng));
+    let record = GameRecord::new(&tictactoe.with_first_player(first));
+    let record = start_game(&mut Seats::new(controllers), record, rng);
+    ask_analysis(&record);
+    ask_save_game(&record);
+}
 
-    fn minimax(&mut self, mut alpha: i8, mut beta: i8) -> i8 {
-        match self.game_over() {
-            GameOver::OnGoing => {}
-            _ => {
-                return self.evaluate();
-            }
+/// Asks for the variant, and the board size if it has one, and plays it.
+fn menu_game(controllers: [Controller; 2], first: Option<Player>) {
+    match ask_variant() {
+        Variant::Classic => new_game(controllers, ask_board_size(), first),
+        Variant::Misere => new_game(controllers, ask_board_size().with_misere(true), first),
+        Variant::Ultimate => {
+            let mut rng = Rng::from_entropy();
+            let first = first.unwrap_or_else(|| Player::random(&mut rng));
+            play_ultimate(Ultimate::new().with_first_player(first), controllers, rng);
         }
+        Variant::Gomoku => {
+            let exact_five =
+                input("Do six or more in a row win too? (Y/n): ").eq_ignore_ascii_case("n");
+            let swap = input("Play the swap opening? (y/N): ").eq_ignore_ascii_case("y");
+            let mut rng = Rng::from_entropy();
+            let first = first.unwrap_or_else(|| Player::random(&mut rng));
+            let gomoku = Gomoku::new(exact_five).with_first_player(first);
+            play_gomoku(gomoku, controllers, swap, rng);
+        }
+    }
+}
 
-        let maximizing = self.turn_to_move() == self.player_1;
-        if maximizing {
-            let mut min_eval: i32 = std::i32::MIN;
-            for pos in self.get_all_moves() {
-                self.make_move(pos);
-                let eval = self.minimax(alpha, beta);
-                self.undo_move(pos);
+fn controller_name(controller: Controller) -> String {
+    match controller {
+        Controller::Human => "Human".to_string(),
+        Controller::Computer(level) => format!("Computer ({})", level),
+        Controller::Engine => "Engine".to_string(),
+        Controller::Remote => "Remote player".to_string(),
+    }
+}
 
-                min_eval = std::cmp::max(min_eval, eval as i32);
-                alpha = std::cmp::max(alpha, eval);
-                if beta <= alpha {
-                    break;
-                }
-            }
-            return min_eval as i8;
-        } else {
-            let mut max_eval: i32 = std::i32::MAX;
-            for pos in self.get_all_moves() {
-                self.make_move(pos);
-                let eval = self.minimax(alpha, beta);
-                self.undo_move(pos);
-
-                max_eval = std::cmp::min(max_eval, eval as i32);
-                beta = std::cmp::min(beta, eval);
-                if beta <= alpha {
-                    break;
-                }
-            }
-            return max_eval as i8;
+fn network_error(address: &str, err: io::Error) -> ! {
+    eprintln!("error: could not connect over {}: {}", address, err);
+    std::process::exit(1);
+}
+
+fn spawn_engine(command: &str) -> ExternalEngine {
+    match ExternalEngine::spawn(command) {
+        Ok(engine) => engine,
+        Err(err) => {
+            eprintln!("error: could not start engine '{}': {}", command, err);
+            std::process::exit(1);
         }
     }
+}
 
-    fn best_move(&mut self) -> i8 {
-        let maximizing = self.turn_to_move() == self.player_1;
-        let mut evaluations_of_moves: Vec<Vec<i8>> = Vec::new();
-        for pos in self.get_all_moves() {
-            self.make_move(pos);
-            evaluations_of_moves.push(vec![pos as i8, self.minimax(std::i8::MIN, std::i8::MAX)]);
-            self.undo_move(pos)
-        }
-        let best_move = if maximizing {
-            evaluations_of_moves.iter().max_by_key(|sub_vec| sub_vec[1])
-        } else {
-            evaluations_of_moves.iter().min_by_key(|sub_vec| sub_vec[1])
-        };
+/// Who plays each side, with the engine processes and the connection to
+/// another machine that some of them need.
+struct Seats {
+    controllers: [Controller; 2],
+    /// The external engine of every [`Controller::Engine`] side.
+    engines: [Option<ExternalEngine>; 2],
+    /// The connection to the [`Controller::Remote`] side, if there is one.
+    remote: Option<Connection>,
+    /// Where spectators follow the game, if anywhere.
+    published: Option<PublishedGame>,
+}
 
-        match best_move {
-            Some(sub_vec) => sub_vec[0],
-            None => -1,
+impl Seats {
+    fn new(controllers: [Controller; 2]) -> Self {
+        Self {
+            controllers,
+            engines: [None, None],
+            remote: None,
+            published: None,
         }
     }
 }
 
-fn main() {
-    clear_terminal();
-    println!("Welcome to the Simpel TicTacToe game");
-    loop {
-        println!("1. Human vs Human\n2. Human vs Computer\n3. Exit");
-        let user_input = input("Pick an option (1, 2, 3): ");
-        match user_input.as_str() {
-            "1" => start_game(false),
-            "2" => start_game(true),
-            "3" => break,
-            _ => println!("Invalid option, please pick a valid option."),
-        }
+/// What [`run_game`] needs to play one kind of game in the terminal.
+trait TerminalGame {
+    /// The commands a human may type instead of a move.
+    const COMMANDS: &'static str = "undo, hint";
+
+    /// Draws the position.
+    fn show(&self);
+
+    fn game_over(&self) -> GameOver;
+
+    fn symbol(&self, player: Player) -> char;
+
+    /// The side whose controller picks the next move.
+    fn seat(&self) -> Player;
+
+    /// Runs before every turn, and returns `true` if it used up the turn
+    /// without a move being played.
+    fn before_turn(&mut self, _controllers: &mut [Controller; 2]) -> bool {
+        false
     }
 
-    println!("Thanks for playing, cya")
+    /// What a human is asked for, e.g. `X's turn`.
+    fn prompt(&self) -> String;
+
+    /// Parses a human's move, or returns `None` if it is not a legal one.
+    fn read_move(&self, text: &str) -> Option<usize>;
+
+    fn hint(&mut self) -> String;
+
+    /// Takes back moves until a human is to move again, or says why it
+    /// cannot.
+    fn undo(&mut self, controllers: [Controller; 2]) -> Result<(), String>;
+
+    /// Plays the moves taken back by [`undo`](Self::undo) again.
+    fn redo(&mut self, _controllers: [Controller; 2]) -> Result<(), String> {
+        Err("Nothing to redo!".to_string())
+    }
+
+    /// The move of a side that is not human, or `None` if there is none and
+    /// the game has to stop.
+    fn machine_move(&mut self, controller: Controller, rng: &mut Rng) -> Option<usize>;
+
+    /// Plays `mv`, or returns `false` if the game cannot go on.
+    fn play(&mut self, mv: usize) -> bool;
 }
 
-fn start_game(vs_computer: bool) {
+/// Plays `game` until it ends: draws it, asks humans for a move, an undo or
+/// a hint, and has the other `controllers` move.
+fn run_game<G: TerminalGame>(game: &mut G, mut controllers: [Controller; 2], rng: &mut Rng) {
     clear_terminal();
-    let mut tictactoe = TicTacToe::new();
+    let demo = !controllers.contains(&Controller::Human);
     loop {
-        tictactoe.print_board();
-        match tictactoe.game_over() {
+        game.show();
+        match game.game_over() {
             GameOver::Draw => {
                 println!("Draw!");
                 break;
             }
             GameOver::Winner(player) => {
-                println!("Player {} has won!", player);
+                println!("Player {} has won!", game.symbol(player));
                 break;
             }
             _ => {}
         }
-        if tictactoe.turn_to_move() == tictactoe.player_1 || !vs_computer {
-            let user_input =
-                input(&format!("\n{}'s turn: ", tictactoe.turn_to_move())).parse::<usize>();
-            if let Ok(value) = user_input {
-                if tictactoe.is_move_valid(value) {
-                    tictactoe.make_move(value);
+        if game.before_turn(&mut controllers) {
+            continue;
+        }
+        let controller = controllers[game.seat().index()];
+        let mv = match controller {
+            Controller::Human => {
+                let user_input = input(&format!("\n{} (or {}): ", game.prompt(), G::COMMANDS));
+                let step = match user_input.as_str() {
+                    "undo" | "u" => Some(game.undo(controllers)),
+                    "redo" | "r" => Some(game.redo(controllers)),
+                    _ => None,
+                };
+                if let Some(step) = step {
+                    clear_terminal();
+                    if let Err(reason) = step {
+                        println!("{}", reason);
+                    }
+                    continue;
+                }
+                if matches!(user_input.as_str(), "hint" | "h") {
+                    let hint = game.hint();
                     clear_terminal();
+                    println!("{}", hint);
                     continue;
                 }
+                match game.read_move(&user_input) {
+                    Some(mv) => mv,
+                    None => {
+                        clear_terminal();
+                        println!("Invalid move!");
+                        continue;
+                    }
+                }
+            }
+            _ => match game.machine_move(controller, rng) {
+                Some(mv) => mv,
+                None => break,
+            },
+        };
+
+        if demo && controller != Controller::Remote {
+            std::thread::sleep(DEMO_MOVE_DELAY);
+        }
+        if !game.play(mv) {
+            break;
+        }
+        clear_terminal();
+    }
+}
+
+/// A classic game with its undo history, played by `seats`.
+struct ClassicGame<'a> {
+    tictactoe: TicTacToe,
+    history: History,
+    seats: &'a mut Seats,
+}
+
+impl ClassicGame<'_> {
+    /// Undoes or redoes moves with `step`, unless the other player is on
+    /// another machine.
+    fn step(&mut self, step: Step, name: &str, controllers: [Controller; 2]) -> Result<(), String> {
+        if self.seats.remote.is_some() {
+            return Err("Moves cannot be taken back in a network game!".to_string());
+        }
+        if !step_history(&mut self.history, &mut self.tictactoe, controllers, step) {
+            return Err(format!("Nothing to {}!", name));
+        }
+        Ok(())
+    }
+}
+
+impl TerminalGame for ClassicGame<'_> {
+    const COMMANDS: &'static str = "undo, redo, hint";
+
+    fn show(&self) {
+        self.tictactoe.print_board();
+    }
+
+    fn game_over(&self) -> GameOver {
+        self.tictactoe.game_over()
+    }
+
+    fn symbol(&self, player: Player) -> char {
+        self.tictactoe.symbol(player)
+    }
+
+    fn seat(&self) -> Player {
+        self.tictactoe.turn_to_move()
+    }
+
+    fn prompt(&self) -> String {
+        format!("{}'s turn", self.symbol(self.seat()))
+    }
+
+    fn read_move(&self, text: &str) -> Option<usize> {
+        text.parse()
+            .ok()
+            .filter(|&mv| self.tictactoe.is_move_valid(mv))
+    }
+
+    fn hint(&mut self) -> String {
+        hint(&mut self.tictactoe)
+    }
+
+    fn undo(&mut self, controllers: [Controller; 2]) -> Result<(), String> {
+        self.step(History::undo, "undo", controllers)
+    }
+
+    fn redo(&mut self, controllers: [Controller; 2]) -> Result<(), String> {
+        self.step(History::redo, "redo", controllers)
+    }
+
+    fn machine_move(&mut self, controller: Controller, rng: &mut Rng) -> Option<usize> {
+        let tictactoe = &mut self.tictactoe;
+        let turn = tictactoe.turn_to_move();
+        match controller {
+            Controller::Computer(level) => tictactoe.computer_move(level, rng),
+            Controller::Engine => {
+                let engine = self.seats.engines[turn.index()]
+                    .as_mut()
+                    .expect("engine sides have an engine");
+                match engine.best_move(tictactoe, DEFAULT_TIME_BUDGET) {
+                    Ok(Some(mv)) if tictactoe.is_move_valid(mv) => Some(mv),
+                    Ok(_) => {
+                        println!("{} did not send a legal move!", engine.name());
+                        None
+                    }
+                    Err(err) => {
+                        println!("{} failed: {}", engine.name(), err);
+                        None
+                    }
+                }
+            }
+            Controller::Remote => {
+                println!("\nWaiting for {}'s move...", tictactoe.symbol(turn));
+                let remote = self
+                    .seats
+                    .remote
+                    .as_mut()
+                    .expect("remote sides have a connection");
+                match remote.receive_move() {
+                    Ok(mv) if tictactoe.is_move_valid(mv) => Some(mv),
+                    Ok(mv) => {
+                        println!("The other player sent an illegal move ({})!", mv);
+                        None
+                    }
+                    Err(err) => {
+                        println!("{}", err);
+                        None
+                    }
+                }
+            }
+            Controller::Human => unreachable!("humans type their moves"),
+        }
+    }
+
+    fn play(&mut self, mv: usize) -> bool {
+        let turn = self.tictactoe.turn_to_move();
+        self.history.play(&mut self.tictactoe, mv);
+        if let Some(published) = &self.seats.published {
+            published.play(mv);
+        }
+        if self.seats.controllers[turn.index()] != Controller::Remote {
+            if let Some(remote) = self.seats.remote.as_mut() {
+                if let Err(err) = remote.send_move(mv) {
+                    println!("Could not send the move: {}", err);
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Plays the game in `record` from its last position until it ends and
+/// returns the record with all moves added.
+fn start_game(seats: &mut Seats, mut record: GameRecord, mut rng: Rng) -> GameRecord {
+    for (player, header) in [(Player::One, "X"), (Player::Two, "O")] {
+        let name = match &seats.engines[player.index()] {
+            Some(engine) => engine.name().to_string(),
+            None => controller_name(seats.controllers[player.index()]),
+        };
+        record.set_header(header, &name);
+    }
+    let controllers = seats.controllers;
+    let mut game = ClassicGame {
+        tictactoe: record
+            .replay()
+            .expect("records are checked when they are created"),
+        history: History::from_moves(record.moves().to_vec()),
+        seats,
+    };
+    run_game(&mut game, controllers, &mut rng);
+    record.set_moves(game.history.moves());
+    record
+}
+
+impl TerminalGame for Ultimate {
+    fn show(&self) {
+        println!("{}", self);
+    }
+
+    fn game_over(&self) -> GameOver {
+        Ultimate::game_over(self)
+    }
+
+    fn symbol(&self, player: Player) -> char {
+        self.meta().symbol(player)
+    }
+
+    fn seat(&self) -> Player {
+        self.turn_to_move()
+    }
+
+    fn prompt(&self) -> String {
+        let wanted = match self.forced_board() {
+            Some(board) => format!("cell on board {}", board),
+            None => "board and cell, e.g. 4 0".to_string(),
+        };
+        format!("{}'s turn, {}", self.symbol(self.seat()), wanted)
+    }
+
+    fn read_move(&self, text: &str) -> Option<usize> {
+        self.parse_move(text).filter(|&mv| self.is_move_valid(mv))
+    }
+
+    fn hint(&mut self) -> String {
+        self.best_move().map_or_else(String::new, |mv| {
+            format!("Suggested move: {} {}", mv / BOARDS, mv % BOARDS)
+        })
+    }
+
+    fn undo(&mut self, controllers: [Controller; 2]) -> Result<(), String> {
+        if self.undo_move().is_none() {
+            return Err("Nothing to undo!".to_string());
+        }
+        while controllers[self.turn_to_move().index()] != Controller::Human
+            && self.undo_move().is_some()
+        {}
+        Ok(())
+    }
+
+    fn machine_move(&mut self, controller: Controller, rng: &mut Rng) -> Option<usize> {
+        match controller {
+            Controller::Computer(level) => self.computer_move(level, rng),
+            _ => unreachable!("ultimate games are only played by humans and the computer"),
+        }
+    }
+
+    fn play(&mut self, mv: usize) -> bool {
+        self.make_move(mv);
+        true
+    }
+}
+
+/// Plays a game of ultimate tic-tac-toe until it ends. Only humans and the
+/// built-in computer can play it.
+fn play_ultimate(mut ultimate: Ultimate, controllers: [Controller; 2], mut rng: Rng) {
+    run_game(&mut ultimate, controllers, &mut rng);
+}
+
+/// A game of gomoku and the state of its swap opening.
+struct GomokuGame {
+    gomoku: Gomoku,
+    swap: bool,
+    /// The player who places every stone of the swap opening.
+    opener: Player,
+    /// The opening stones the computer places as the opener.
+    opening: [usize; SWAP_STONES],
+    /// Whether the second player has picked a side, or there is no swap
+    /// opening.
+    side_picked: bool,
+}
+
+impl TerminalGame for GomokuGame {
+    fn show(&self) {
+        println!("{}", self.gomoku);
+    }
+
+    fn game_over(&self) -> GameOver {
+        self.gomoku.game_over()
+    }
+
+    fn symbol(&self, player: Player) -> char {
+        self.gomoku.board().symbol(player)
+    }
+
+    fn seat(&self) -> Player {
+        if self.side_picked {
+            self.gomoku.turn_to_move()
+        } else {
+            self.opener
+        }
+    }
+
+    /// Lets the second player pick a side once the swap opening is placed.
+    fn before_turn(&mut self, controllers: &mut [Controller; 2]) -> bool {
+        if self.side_picked || self.gomoku.move_count() < SWAP_STONES {
+            return false;
+        }
+        self.side_picked = true;
+        let turn = self.gomoku.turn_to_move();
+        let swapped = match controllers[turn.index()] {
+            Controller::Human => input(&format!(
+                "\nKeep playing {} or swap and take over {}? (keep/swap): ",
+                self.symbol(turn),
+                self.symbol(self.opener)
+            ))
+            .eq_ignore_ascii_case("swap"),
+            Controller::Computer(_) => self.gomoku.prefers_swap(),
+            Controller::Engine | Controller::Remote => {
+                unreachable!("gomoku games are only played by humans and the computer")
             }
+        };
+        clear_terminal();
+        if swapped {
+            controllers.swap(0, 1);
+            println!(
+                "The second player swapped and plays {}.",
+                self.symbol(self.opener)
+            );
         } else {
-            let mv = tictactoe.best_move();
-            tictactoe.make_move(mv as usize);
-            clear_terminal();
-            continue;
+            println!("The second player keeps {}.", self.symbol(turn));
+        }
+        true
+    }
+
+    fn prompt(&self) -> String {
+        let turn = self.gomoku.turn_to_move();
+        if self.side_picked {
+            format!("{}'s turn, e.g. h8", self.symbol(turn))
+        } else {
+            format!(
+                "Opening stone {} of {}, for {}, e.g. h8",
+                self.gomoku.move_count() + 1,
+                SWAP_STONES,
+                self.symbol(turn)
+            )
+        }
+    }
+
+    fn read_move(&self, text: &str) -> Option<usize> {
+        self.gomoku
+            .parse_move(text)
+            .filter(|&mv| self.gomoku.is_move_valid(mv))
+    }
+
+    fn hint(&mut self) -> String {
+        self.gomoku.best_move().map_or_else(String::new, |mv| {
+            format!("Suggested move: {}", self.gomoku.board().coordinate(mv))
+        })
+    }
+
+    fn undo(&mut self, controllers: [Controller; 2]) -> Result<(), String> {
+        let fixed_moves = if self.swap { SWAP_STONES } else { 0 };
+        if self.gomoku.move_count() == 0 {
+            return Err("Nothing to undo!".to_string());
+        }
+        if self.gomoku.move_count() <= fixed_moves {
+            return Err("The swap opening cannot be taken back!".to_string());
+        }
+        self.gomoku.undo_move();
+        while controllers[self.gomoku.turn_to_move().index()] != Controller::Human
+            && self.gomoku.move_count() > fixed_moves
+            && self.gomoku.undo_move().is_some()
+        {}
+        Ok(())
+    }
+
+    fn machine_move(&mut self, controller: Controller, rng: &mut Rng) -> Option<usize> {
+        match controller {
+            Controller::Computer(_) if !self.side_picked => {
+                Some(self.opening[self.gomoku.move_count()])
+            }
+            Controller::Computer(level) => self.gomoku.computer_move(level, rng),
+            _ => unreachable!("gomoku games are only played by humans and the computer"),
+        }
+    }
+
+    fn play(&mut self, mv: usize) -> bool {
+        self.gomoku.make_move(mv);
+        true
+    }
+}
+
+/// Plays a game of gomoku until it ends, starting with the swap opening if
+/// `swap` is set. Only humans and the built-in computer can play it.
+fn play_gomoku(gomoku: Gomoku, controllers: [Controller; 2], swap: bool, mut rng: Rng) {
+    let mut game = GomokuGame {
+        swap,
+        opener: gomoku.turn_to_move(),
+        opening: gomoku.swap_opening(&mut rng),
+        side_picked: !swap,
+        gomoku,
+    };
+    run_game(&mut game, controllers, &mut rng);
+}
+
+/// The engine's suggestion for the side to move and the verdict on every
+/// legal move.
+fn hint(tictactoe: &mut TicTacToe) -> String {
+    let mut lines = Vec::new();
+    if let Some(mv) = tictactoe.best_move() {
+        lines.push(format!("Suggested move: {}", mv));
+    }
+    for evaluation in tictactoe.analyze_moves(DEFAULT_TIME_BUDGET) {
+        let verdict = tictactoe.verdict(evaluation.score, evaluation.exact);
+        lines.push(format!("  {:>3}: {}", evaluation.mv, verdict));
+    }
+    lines.join("\n")
+}
+
+/// [`History::undo`] or [`History::redo`].
+type Step = fn(&mut History, &mut TicTacToe) -> Option<usize>;
+
+/// Undoes or redoes one move with `step`, then keeps going while it is a
+/// computer's turn, so a human playing the computer gets back to their own
+/// turn. Returns `false` if there was nothing to undo or redo.
+fn step_history(
+    history: &mut History,
+    tictactoe: &mut TicTacToe,
+    controllers: [Controller; 2],
+    step: Step,
+) -> bool {
+    if step(history, tictactoe).is_none() {
+        return false;
+    }
+    while controllers[tictactoe.turn_to_move().index()] != Controller::Human
+        && step(history, tictactoe).is_some()
+    {}
+    true
+}
+
+fn ask_analysis(record: &GameRecord) {
+    if input("Analyze the game? (y/N): ").eq_ignore_ascii_case("y") {
+        print_analysis(record);
+    }
+}
+
+/// Prints every move of the game with the engine's grade, and how many
+/// inaccuracies and blunders each side made.
+fn print_analysis(record: &GameRecord) {
+    let start = record.start();
+    let analysis = analyze_game(start, record.moves(), ANALYSIS_TIME_PER_MOVE);
+    println!("\nAnalysis:");
+    for (index, annotated) in analysis.iter().enumerate() {
+        let symbol = start.symbol(annotated.player);
+        let note = match annotated.quality {
+            MoveQuality::Best => String::new(),
+            MoveQuality::Inaccuracy | MoveQuality::Blunder => format!(
+                " {}: {} instead of {} (best was {})",
+                if annotated.quality == MoveQuality::Blunder {
+                    "blunder"
+                } else {
+                    "inaccuracy"
+                },
+                annotated.played,
+                annotated.best,
+                annotated.best_move
+            ),
+        };
+        println!("{:>3}. {} {:>3}{}", index + 1, symbol, annotated.mv, note);
+    }
+    for player in [Player::One, Player::Two] {
+        let (inaccuracies, blunders) = analysis::mistakes(&analysis, player);
+        println!(
+            "{}: {} inaccuracies, {} blunders",
+            start.symbol(player),
+            inaccuracies,
+            blunders
+        );
+    }
+}
+
+fn load_game(path: &std::path::Path) -> GameRecord {
+    match GameRecord::load(path) {
+        Ok(record) => record,
+        Err(err) => {
+            eprintln!("error: could not load {}: {}", path.display(), err);
+            std::process::exit(1);
         }
+    }
+}
+
+fn ask_save_game(record: &GameRecord) {
+    let path = input("Save the game to a file (leave empty to skip): ");
+    if !path.is_empty() {
+        save_game(record, std::path::Path::new(&path));
+    }
+}
+
+fn save_game(record: &GameRecord, path: &std::path::Path) {
+    match record.save(path) {
+        Ok(()) => println!("Game saved to {}", path.display()),
+        Err(err) => eprintln!("Could not save the game to {}: {}", path.display(), err),
+    }
+}
+
+/// Shows every position of a saved game, one move per press of Enter.
+fn replay(record: &GameRecord) {
+    for count in 0..=record.moves().len() {
         clear_terminal();
-        println!("Invalid number!");
+        for name in ["Event", "Date", "X", "O"] {
+            if let Some(value) = record.header(name) {
+                println!("{}: {}", name, value);
+            }
+        }
+        println!("Move {} of {}\n", count, record.moves().len());
+        let tictactoe = record
+            .position_after(count)
+            .expect("records are checked when they are loaded");
+        tictactoe.print_board();
+        if count < record.moves().len() {
+            input("\nPress Enter for the next move");
+        }
     }
+    println!("\nResult: {}", record.result());
 }
 
 fn input(msg: &str) -> String {
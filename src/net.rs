@@ -0,0 +1,420 @@
+//! Two players on different machines, connected over TCP.
+//!
+//! One side hosts: it listens on a port, picks the board and sides, and
+//! waits for the other side to join. Both then send their moves to each
+//! other. The protocol is line based text:
+//!
+//! - `game <notation> <side>`, host to guest: the start position in the
+//!   [notation](crate::notation), and `x` or `o` for the guest's side.
+//! - `ready`, guest to host: the guest accepts the game.
+//! - `move <cell>`, either way: the sender played `cell`.
+//! - `bye`, either way: the sender leaves the game.
+//!
+//! Each side checks the other's moves with
+//! [`TicTacToe::is_move_valid`] and drops the connection on an illegal one.
+//!
+//! A game server hosts the games of many players instead. A player asks it
+//! for a game with one of these before the server sends `game`:
+//!
+//! - `queue <variant>`: play the next player queued for the same variant,
+//!   given as `<width>x<height>/<win length>` like in
+//!   [records](crate::record).
+//! - `create <variant>`: open a lobby, answered with `lobby <id> <variant>`,
+//!   and wait for someone to join it.
+//! - `join <id>`: play the player waiting in lobby `id`.
+//! - `list`: answered with `lobby <id> <variant>` for every open lobby,
+//!   then `end`.
+//!
+//! The server answers requests and moves it refuses with `error <reason>`.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+
+use crate::game::{Player, TicTacToe};
+
+/// Port used when an address does not name one.
+pub const DEFAULT_PORT: u16 = 7711;
+
+/// One line of the protocol.
+#[derive(Debug, Clone)]
+pub enum Message {
+    Game {
+        start: Box<TicTacToe>,
+        guest: Player,
+    },
+    Ready,
+    Move(usize),
+    Bye,
+    Queue(String),
+    Create(String),
+    Join(u32),
+    List,
+    Lobby {
+        id: u32,
+        variant: String,
+    },
+    End,
+    Error(String),
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Game { start, guest } => write!(
+                f,
+                "game {} {}",
+                start.to_notation(),
+                if *guest == Player::One { 'x' } else { 'o' }
+            ),
+            Message::Ready => write!(f, "ready"),
+            Message::Move(mv) => write!(f, "move {}", mv),
+            Message::Bye => write!(f, "bye"),
+            Message::Queue(variant) => write!(f, "queue {}", variant),
+            Message::Create(variant) => write!(f, "create {}", variant),
+            Message::Join(id) => write!(f, "join {}", id),
+            Message::List => write!(f, "list"),
+            Message::Lobby { id, variant } => write!(f, "lobby {} {}", id, variant),
+            Message::End => write!(f, "end"),
+            Message::Error(reason) => write!(f, "error {}", reason),
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "game" => {
+                let (notation, guest) = argument
+                    .rsplit_once(' ')
+                    .ok_or_else(|| format!("invalid game message '{}'", line))?;
+                let start = TicTacToe::from_notation(notation).map_err(|err| err.to_string())?;
+                let guest = match guest {
+                    "x" => Player::One,
+                    "o" => Player::Two,
+                    _ => return Err(format!("unknown side '{}'", guest)),
+                };
+                Ok(Message::Game {
+                    start: Box::new(start),
+                    guest,
+                })
+            }
+            "ready" => Ok(Message::Ready),
+            "move" => argument
+                .parse()
+                .map(Message::Move)
+                .map_err(|_| format!("invalid move '{}'", argument)),
+            "bye" => Ok(Message::Bye),
+            "queue" => Ok(Message::Queue(argument.to_string())),
+            "create" => Ok(Message::Create(argument.to_string())),
+            "join" => argument
+                .parse()
+                .map(Message::Join)
+                .map_err(|_| format!("invalid lobby '{}'", argument)),
+            "list" => Ok(Message::List),
+            "lobby" => {
+                let (id, variant) = argument.split_once(' ').unwrap_or((argument, ""));
+                let id = id.parse().map_err(|_| format!("invalid lobby '{}'", id))?;
+                Ok(Message::Lobby {
+                    id,
+                    variant: variant.to_string(),
+                })
+            }
+            "end" => Ok(Message::End),
+            "error" => Ok(Message::Error(argument.to_string())),
+            _ => Err(format!("unknown message '{}'", line)),
+        }
+    }
+}
+
+/// A connection to the other player.
+#[derive(Debug)]
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    /// Waits on `listener` for a guest, offers it the game from `start`
+    /// with the guest playing `guest`, and waits until it accepts.
+    pub fn host(listener: &TcpListener, start: &TicTacToe, guest: Player) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+        let mut connection = Self::new(stream)?;
+        connection.send(&Message::Game {
+            start: Box::new(start.clone()),
+            guest,
+        })?;
+        match connection.receive()? {
+            Message::Ready => Ok(connection),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    /// Joins the game hosted at `address` and returns the connection, the
+    /// start position and the side this player plays. `request` is sent
+    /// first when joining a game server, e.g. [`Message::Queue`]; the lobby
+    /// a [`Message::Create`] opens is reported to `on_lobby`.
+    pub fn join(
+        address: impl ToSocketAddrs,
+        request: Option<&Message>,
+        mut on_lobby: impl FnMut(u32),
+    ) -> io::Result<(Self, TicTacToe, Player)> {
+        let mut connection = Self::new(TcpStream::connect(address)?)?;
+        if let Some(request) = request {
+            connection.send(request)?;
+        }
+        loop {
+            match connection.receive()? {
+                Message::Game { start, guest } => {
+                    connection.send(&Message::Ready)?;
+                    return Ok((connection, *start, guest));
+                }
+                Message::Lobby { id, .. } => on_lobby(id),
+                other => return Err(unexpected(&other)),
+            }
+        }
+    }
+
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        writeln!(self.writer, "{}", message)?;
+        self.writer.flush()
+    }
+
+    /// Reads the next message, failing if the other side hung up.
+    pub fn receive(&mut self) -> io::Result<Message> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the other player disconnected",
+            ));
+        }
+        line.parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Whether the other side has hung up or said [`Message::Bye`], checked
+    /// without waiting. Meant for connections that are waiting for a game,
+    /// which have nothing else to say.
+    pub fn is_closed(&mut self) -> bool {
+        if self.writer.set_nonblocking(true).is_err() {
+            return true;
+        }
+        let closed = match self.reader.fill_buf() {
+            Ok(buffered) => buffered.is_empty() || buffered.starts_with(b"bye"),
+            Err(err) => err.kind() != io::ErrorKind::WouldBlock,
+        };
+        self.writer.set_nonblocking(false).is_err() || closed
+    }
+
+    pub fn send_move(&mut self, mv: usize) -> io::Result<()> {
+        self.send(&Message::Move(mv))
+    }
+
+    /// Waits for the other player's move.
+    pub fn receive_move(&mut self) -> io::Result<usize> {
+        match self.receive()? {
+            Message::Move(mv) => Ok(mv),
+            Message::Bye => Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "the other player left the game",
+            )),
+            Message::Error(reason) => Err(io::Error::other(reason)),
+            other => Err(unexpected(&other)),
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.send(&Message::Bye);
+    }
+}
+
+/// Completes `address`: a bare port gets `default_host`, and a host
+/// without a port gets [`DEFAULT_PORT`].
+pub fn complete_address(address: &str, default_host: &str) -> String {
+    if address.parse::<u16>().is_ok() {
+        format!("{}:{}", default_host, address)
+    } else if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, DEFAULT_PORT)
+    }
+}
+
+fn unexpected(message: &Message) -> io::Error {
+    match message {
+        Message::Error(reason) => io::Error::other(reason.clone()),
+        _ => io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected message '{}'", message),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// A host and a guest connected over localhost, playing on `start`.
+    fn connect(start: &TicTacToe, guest: Player) -> (Connection, Connection, TicTacToe, Player) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let guest_thread = thread::spawn(move || Connection::join(address, None, |_| {}).unwrap());
+        let host = Connection::host(&listener, start, guest).unwrap();
+        let (guest, start, side) = guest_thread.join().unwrap();
+        (host, guest, start, side)
+    }
+
+    #[test]
+    fn round_trips_messages() {
+        for line in [
+            "game x1o/1x1/3 o 3 3 x",
+            "game 3/3/3 x 3m 0 o",
+            "ready",
+            "move 4",
+            "bye",
+            "queue 3x3/3",
+            "create 4x4/3",
+            "join 7",
+            "list",
+            "lobby 7 4x4/3",
+            "end",
+            "error no such lobby",
+        ] {
+            let message: Message = line.parse().unwrap();
+            assert_eq!(message.to_string(), line);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_messages() {
+        for line in [
+            "",
+            "dance",
+            "move",
+            "move four",
+            "move -1",
+            "join x",
+            "lobby x 3x3/3",
+            "game 3/3/3 x 3",
+            "game 3/3/3 x 3 0 z",
+            "game 3/2/3 x 3 x",
+        ] {
+            assert!(line.parse::<Message>().is_err(), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn plays_over_localhost() {
+        let start = TicTacToe::with_size(4, 4, 3);
+        let (mut host, mut guest, guest_start, side) = connect(&start, Player::Two);
+        assert_eq!(guest_start.to_notation(), start.to_notation());
+        assert_eq!(side, Player::Two);
+
+        let (mut host_game, mut guest_game) = (start.clone(), guest_start);
+        for (index, mv) in [5, 0, 6, 1, 7].into_iter().enumerate() {
+            let (sender, receiver) = if index % 2 == 0 {
+                (&mut host, &mut guest)
+            } else {
+                (&mut guest, &mut host)
+            };
+            sender.send_move(mv).unwrap();
+            let received = receiver.receive_move().unwrap();
+            assert_eq!(received, mv);
+            assert!(host_game.is_move_valid(received));
+            host_game.make_move(received);
+            guest_game.make_move(received);
+        }
+        assert_eq!(host_game.to_notation(), guest_game.to_notation());
+        assert_eq!(
+            host_game.game_over(),
+            crate::game::GameOver::Winner(Player::One)
+        );
+    }
+
+    #[test]
+    fn reports_unexpected_and_invalid_messages() {
+        let (mut host, mut guest, mut tictactoe, _) = connect(&TicTacToe::new(), Player::Two);
+
+        // A message that is not a move when a move is expected.
+        guest.send(&Message::Ready).unwrap();
+        let err = host.receive_move().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // A line that is not a message at all.
+        writeln!(guest.writer, "castle kingside").unwrap();
+        let err = host.receive_move().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // A move out of turn, onto a taken cell, arrives but is not valid.
+        tictactoe.make_move(4);
+        guest.send_move(4).unwrap();
+        let mv = host.receive_move().unwrap();
+        assert!(!tictactoe.is_move_valid(mv));
+
+        guest
+            .send(&Message::Error("no such lobby".to_string()))
+            .unwrap();
+        let err = host.receive_move().unwrap_err();
+        assert_eq!(err.to_string(), "no such lobby");
+
+        drop(guest);
+        let err = host.receive_move().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+        let err = host.receive_move().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn notices_when_the_other_side_leaves() {
+        let (mut host, guest, _, _) = connect(&TicTacToe::new(), Player::Two);
+        assert!(!host.is_closed());
+        drop(guest);
+        // Give the goodbye time to arrive.
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert!(host.is_closed());
+
+        let (mut host, guest, _, _) = connect(&TicTacToe::new(), Player::Two);
+        guest.writer.shutdown(std::net::Shutdown::Both).unwrap();
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert!(host.is_closed());
+    }
+
+    #[test]
+    fn join_fails_on_an_unexpected_answer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut connection = Connection::new(stream).unwrap();
+            connection.send(&Message::Move(4)).unwrap();
+        });
+        assert!(Connection::join(address, None, |_| {}).is_err());
+        host.join().unwrap();
+    }
+
+    #[test]
+    fn completes_addresses() {
+        assert_eq!(complete_address("8000", "localhost"), "localhost:8000");
+        assert_eq!(
+            complete_address("example.org", "localhost"),
+            "example.org:7711"
+        );
+        assert_eq!(complete_address("10.0.0.1:99", "localhost"), "10.0.0.1:99");
+    }
+}
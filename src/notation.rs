@@ -0,0 +1,505 @@
+//! A compact, FEN-like text notation for positions.
+//!
+//! A position is written as four space separated fields:
+//!
+//! ```text
+//! x1o/1x1/3 o 3 3
+//! ```
+//!
+//! 1. The board, row by row from the top, rows separated by `/`. `x` is a
+//!    mark of [`Player::One`], `o` one of [`Player::Two`], and a number is
+//!    that many empty cells. Every row must have the same width.
+//! 2. The side to move, `x` or `o`.
+//! 3. The number of marks in a row needed to win, at most the longer side
+//!    of the board, followed by `m` for a
+//!    [misère](TicTacToe::with_misere) game where completing a line loses
+//!    and `e` if only lines of [exactly](TicTacToe::with_exact_length) that
+//!    length count, e.g. `3m` or `5e`.
+//! 4. Optionally, the number of moves played so far, which is at least the
+//!    number of marks on the board and at most the number of cells. It
+//!    defaults to the number of marks.
+//!
+//! Display symbols are not part of the notation, it always uses `x` and `o`.
+//!
+//! Single cells can also be written as coordinates, as on Go and Gomoku
+//! boards: a column letter from `a` on the left and a row number from `1`
+//! at the bottom, so `h8` is the centre of a 15×15 board. Columns after `z`
+//! continue with `aa`, `ab` and so on.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bitboard::MAX_CELLS;
+use crate::game::{check_board, Cell, Player, TicTacToe};
+
+/// Why a position string could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A required field is missing.
+    MissingField(&'static str),
+    /// There are more than four fields.
+    UnexpectedField(String),
+    /// A board row contains something other than `x`, `o` or a number.
+    InvalidCell { row: usize, found: char },
+    /// A board row is not as wide as the first one.
+    RaggedRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The board is empty, has more than [`MAX_CELLS`] cells, or is
+    /// shorter than the win length on both sides.
+    InvalidSize { width: usize, height: usize },
+    /// The side to move is not `x` or `o`.
+    InvalidSide(String),
+    /// A numeric field is not a number, or out of range.
+    InvalidNumber { field: &'static str, value: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::MissingField(field) => write!(f, "missing {}", field),
+            ParseError::UnexpectedField(field) => write!(f, "unexpected field '{}'", field),
+            ParseError::InvalidCell { row, found } => {
+                write!(f, "invalid cell '{}' in row {}", found, row + 1)
+            }
+            ParseError::RaggedRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} cells, expected {}",
+                row + 1,
+                found,
+                expected
+            ),
+            ParseError::InvalidSize { width, height } => write!(
+                f,
+                "a {}x{} board is not supported, it must have between 1 and {} cells \
+                 and a side at least as long as the win length",
+                width, height, MAX_CELLS
+            ),
+            ParseError::InvalidSide(side) => {
+                write!(f, "invalid side to move '{}', expected x or o", side)
+            }
+            ParseError::InvalidNumber { field, value } => {
+                write!(f, "invalid {} '{}'", field, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl TicTacToe {
+    /// Writes the position in the notation described in the [module
+    /// docs](self).
+    pub fn to_notation(&self) -> String {
+        let rows: Vec<String> = (0..self.height())
+            .map(|row| {
+                let mut text = String::new();
+                let mut empty = 0;
+                for col in 0..self.width() {
+                    match self.cell(row * self.width() + col) {
+                        Cell::Empty => empty += 1,
+                        Cell::Occupied(player) => {
+                            if empty > 0 {
+                                text.push_str(&empty.to_string());
+                                empty = 0;
+                            }
+                            text.push(player_char(player));
+                        }
+                    }
+                }
+                if empty > 0 {
+                    text.push_str(&empty.to_string());
+                }
+                text
+            })
+            .collect();
+        format!(
+            "{} {} {}{} {}",
+            rows.join("/"),
+            player_char(self.turn_to_move()),
+            self.win_length(),
+            rules_suffix(self),
+            self.move_count()
+        )
+    }
+
+    /// Reads a position written in the notation described in the [module
+    /// docs](self).
+    pub fn from_notation(text: &str) -> Result<Self, ParseError> {
+        let mut fields = text.split_whitespace();
+        let board = fields.next().ok_or(ParseError::MissingField("board"))?;
+        let side = fields
+            .next()
+            .ok_or(ParseError::MissingField("side to move"))?;
+        let win_length = fields
+            .next()
+            .ok_or(ParseError::MissingField("win length"))?;
+        let move_count = fields.next();
+        if let Some(field) = fields.next() {
+            return Err(ParseError::UnexpectedField(field.to_string()));
+        }
+
+        let mut rows: Vec<Vec<Cell>> = Vec::new();
+        for (row, text) in board.split('/').enumerate() {
+            let cells = parse_row(row, text)?;
+            if let Some(first) = rows.first() {
+                let expected = first.len();
+                if cells.len() != expected {
+                    return Err(ParseError::RaggedRow {
+                        row,
+                        expected,
+                        found: cells.len(),
+                    });
+                }
+            }
+            rows.push(cells);
+        }
+        let (width, height) = (rows[0].len(), rows.len());
+
+        let side = match side {
+            "x" | "X" => Player::One,
+            "o" | "O" => Player::Two,
+            _ => return Err(ParseError::InvalidSide(side.to_string())),
+        };
+        let (win_length, misere, exact_length) = split_rules(win_length);
+        let win_length = match win_length.parse() {
+            Ok(win_length) if win_length > 0 => win_length,
+            _ => {
+                return Err(ParseError::InvalidNumber {
+                    field: "win length",
+                    value: win_length.to_string(),
+                })
+            }
+        };
+        if check_board(width, height, win_length).is_err() {
+            return Err(ParseError::InvalidSize { width, height });
+        }
+
+        let mut tictactoe = TicTacToe::with_size(width, height, win_length)
+            .with_misere(misere)
+            .with_exact_length(exact_length);
+        for (pos, cell) in rows.into_iter().flatten().enumerate() {
+            if let Cell::Occupied(player) = cell {
+                tictactoe.place(pos, player);
+            }
+        }
+        tictactoe.set_turn_to_move(side);
+        let marks = tictactoe.size() - tictactoe.get_all_moves().len();
+        let move_count = match move_count {
+            Some(value) => match value.parse() {
+                Ok(count) if (marks..=tictactoe.size()).contains(&count) => count,
+                _ => {
+                    return Err(ParseError::InvalidNumber {
+                        field: "move count",
+                        value: value.to_string(),
+                    })
+                }
+            },
+            None => marks,
+        };
+        tictactoe.set_move_count(move_count);
+        Ok(tictactoe)
+    }
+}
+
+impl TicTacToe {
+    /// Writes cell `pos` as a coordinate, see the [module docs](self).
+    pub fn coordinate(&self, pos: usize) -> String {
+        let (mut column, row) = (pos % self.width(), pos / self.width());
+        let mut letters = Vec::new();
+        loop {
+            letters.push((b'a' + (column % 26) as u8) as char);
+            if column < 26 {
+                break;
+            }
+            column = column / 26 - 1;
+        }
+        letters.iter().rev().collect::<String>() + &(self.height() - row).to_string()
+    }
+
+    /// Reads a coordinate written by [`coordinate`](Self::coordinate), in
+    /// either case. `None` if it is not a cell of this board.
+    pub fn parse_coordinate(&self, text: &str) -> Option<usize> {
+        let text = text.trim().to_ascii_lowercase();
+        let digits = text.find(|c: char| c.is_ascii_digit())?;
+        let (letters, number) = text.split_at(digits);
+        if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_lowercase()) {
+            return None;
+        }
+        let column = letters.bytes().try_fold(0usize, |column, letter| {
+            column
+                .checked_mul(26)?
+                .checked_add((letter - b'a') as usize + 1)
+        })? - 1;
+        let row: usize = number.parse().ok()?;
+        if column >= self.width() || row == 0 || row > self.height() {
+            return None;
+        }
+        Some((self.height() - row) * self.width() + column)
+    }
+}
+
+impl FromStr for TicTacToe {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_notation(s)
+    }
+}
+
+/// The rule letters written after a win length: `m` for misère games and
+/// `e` for exact-length ones.
+pub(crate) fn rules_suffix(tictactoe: &TicTacToe) -> &'static str {
+    match (tictactoe.is_misere(), tictactoe.is_exact_length()) {
+        (false, false) => "",
+        (true, false) => "m",
+        (false, true) => "e",
+        (true, true) => "me",
+    }
+}
+
+/// Splits the rule letters of [`rules_suffix`] off the end of `text`,
+/// returning the rest and whether the game is misère and exact-length.
+pub(crate) fn split_rules(text: &str) -> (&str, bool, bool) {
+    let rest = text.trim_end_matches(['m', 'e']);
+    let rules = &text[rest.len()..];
+    (rest, rules.contains('m'), rules.contains('e'))
+}
+
+fn player_char(player: Player) -> char {
+    match player {
+        Player::One => 'x',
+        Player::Two => 'o',
+    }
+}
+
+fn parse_row(row: usize, text: &str) -> Result<Vec<Cell>, ParseError> {
+    let mut cells = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            'x' | 'X' => cells.push(Cell::Occupied(Player::One)),
+            'o' | 'O' => cells.push(Cell::Occupied(Player::Two)),
+            '1'..='9' => {
+                let mut digits = c.to_string();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(digit);
+                }
+                let empty: usize = digits.parse().map_err(|_| ParseError::InvalidNumber {
+                    field: "empty cell count",
+                    value: digits.clone(),
+                })?;
+                if cells.len() + empty > MAX_CELLS {
+                    return Err(ParseError::InvalidNumber {
+                        field: "empty cell count",
+                        value: digits,
+                    });
+                }
+                cells.extend(std::iter::repeat_n(Cell::Empty, empty));
+            }
+            found => return Err(ParseError::InvalidCell { row, found }),
+        }
+    }
+    Ok(cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        for notation in [
+            "3/3/3 x 3 0",
+            "x1o/1x1/3 o 3 3",
+            "xo2/4/2ox/o3 x 3 5",
+            "x5/6 o 4 1",
+            "3/1x1/3 o 3m 1",
+            "15/15/15 x 5e 0",
+            "3/3/3 x 3me 0",
+        ] {
+            let tictactoe = TicTacToe::from_notation(notation).unwrap();
+            assert_eq!(tictactoe.to_notation(), notation);
+        }
+    }
+
+    #[test]
+    fn reads_the_board() {
+        let tictactoe: TicTacToe = "x1o/1x1/2o x 3".parse().unwrap();
+        assert_eq!((tictactoe.width(), tictactoe.height()), (3, 3));
+        assert_eq!(tictactoe.cell(0), Cell::Occupied(Player::One));
+        assert_eq!(tictactoe.cell(1), Cell::Empty);
+        assert_eq!(tictactoe.cell(2), Cell::Occupied(Player::Two));
+        assert_eq!(tictactoe.cell(8), Cell::Occupied(Player::Two));
+        assert_eq!(tictactoe.turn_to_move(), Player::One);
+        // The move count defaults to the number of marks.
+        assert_eq!(tictactoe.move_count(), 4);
+    }
+
+    #[test]
+    fn reads_the_rules() {
+        let misere = TicTacToe::from_notation("3/3/3 x 3m").unwrap();
+        assert!(misere.is_misere());
+        assert!(!misere.is_exact_length());
+        let exact = TicTacToe::from_notation("5/5/5/5/5 o 5e").unwrap();
+        assert!(exact.is_exact_length());
+        assert!(!exact.is_misere());
+        assert!(!TicTacToe::from_notation("3/3/3 x 3").unwrap().is_misere());
+    }
+
+    #[test]
+    fn misere_positions_are_lost_by_the_line_maker() {
+        let tictactoe = TicTacToe::from_notation("xxx/oo1/3 o 3m").unwrap();
+        assert_eq!(
+            tictactoe.game_over(),
+            crate::game::GameOver::Winner(Player::Two)
+        );
+    }
+
+    #[test]
+    fn rejects_ragged_rows() {
+        assert_eq!(
+            TicTacToe::from_notation("3/2/3 x 3").unwrap_err(),
+            ParseError::RaggedRow {
+                row: 1,
+                expected: 3,
+                found: 2
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_bad_symbols() {
+        assert_eq!(
+            TicTacToe::from_notation("x1o/1z1/3 x 3").unwrap_err(),
+            ParseError::InvalidCell { row: 1, found: 'z' }
+        );
+        assert_eq!(
+            TicTacToe::from_notation("3/3/3 y 3").unwrap_err(),
+            ParseError::InvalidSide("y".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_bad_win_lengths() {
+        for win_length in ["0", "three", "-1", "m", "3x"] {
+            assert_eq!(
+                TicTacToe::from_notation(&format!("3/3/3 x {}", win_length)).unwrap_err(),
+                ParseError::InvalidNumber {
+                    field: "win length",
+                    value: win_length.trim_end_matches(['m', 'e']).to_string()
+                },
+                "win length '{}'",
+                win_length
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_missing_and_extra_fields() {
+        assert_eq!(
+            TicTacToe::from_notation("").unwrap_err(),
+            ParseError::MissingField("board")
+        );
+        assert_eq!(
+            TicTacToe::from_notation("3/3/3").unwrap_err(),
+            ParseError::MissingField("side to move")
+        );
+        assert_eq!(
+            TicTacToe::from_notation("3/3/3 x").unwrap_err(),
+            ParseError::MissingField("win length")
+        );
+        assert_eq!(
+            TicTacToe::from_notation("3/3/3 x 3 0 extra").unwrap_err(),
+            ParseError::UnexpectedField("extra".to_string())
+        );
+        assert!(matches!(
+            TicTacToe::from_notation("3/3/3 x 3 many"),
+            Err(ParseError::InvalidNumber {
+                field: "move count",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_win_lengths_longer_than_the_board() {
+        assert_eq!(
+            TicTacToe::from_notation("3/3/3 x 4").unwrap_err(),
+            ParseError::InvalidSize {
+                width: 3,
+                height: 3
+            }
+        );
+        // Used to overflow when the lines were set up.
+        assert_eq!(
+            TicTacToe::from_notation("3/3/3 x 9223372036854775808").unwrap_err(),
+            ParseError::InvalidSize {
+                width: 3,
+                height: 3
+            }
+        );
+        assert!(TicTacToe::from_notation("5/5 x 5").is_ok());
+    }
+
+    #[test]
+    fn rejects_move_counts_out_of_range() {
+        // Fewer moves than marks, more moves than cells, and counts that
+        // used to overflow when scoring or playing a move.
+        for notation in [
+            "xo1/3/3 x 3 1",
+            "3/3/3 x 3 10",
+            "xxx/oo1/3 o 3 2147483648",
+            "3/3/3 x 3 18446744073709551615",
+        ] {
+            assert!(
+                matches!(
+                    TicTacToe::from_notation(notation),
+                    Err(ParseError::InvalidNumber {
+                        field: "move count",
+                        ..
+                    })
+                ),
+                "{}",
+                notation
+            );
+        }
+        for (notation, move_count) in [("xo1/3/3 x 3 2", 2), ("xo1/3/3 x 3 9", 9)] {
+            let tictactoe = TicTacToe::from_notation(notation).unwrap();
+            assert_eq!(tictactoe.move_count(), move_count);
+        }
+    }
+
+    #[test]
+    fn rejects_boards_that_are_too_big() {
+        assert!(matches!(
+            TicTacToe::from_notation("17/17/17/17/17/17/17/17/17/17/17/17/17/17/17/17 x 5"),
+            Err(ParseError::InvalidSize {
+                width: 17,
+                height: 16
+            })
+        ));
+    }
+
+    #[test]
+    fn coordinates_round_trip() {
+        let tictactoe = TicTacToe::with_size(15, 15, 5);
+        assert_eq!(tictactoe.coordinate(7 * 15 + 7), "h8");
+        assert_eq!(tictactoe.coordinate(0), "a15");
+        assert_eq!(tictactoe.coordinate(224), "o1");
+        for pos in 0..tictactoe.size() {
+            let coordinate = tictactoe.coordinate(pos);
+            assert_eq!(tictactoe.parse_coordinate(&coordinate), Some(pos));
+        }
+        assert_eq!(tictactoe.parse_coordinate("H8"), Some(7 * 15 + 7));
+        for invalid in ["", "h", "8", "p1", "a0", "a16", "8h", "h-1"] {
+            assert_eq!(tictactoe.parse_coordinate(invalid), None, "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! A line based, UCI-style protocol between a front end and an engine.
+//!
+//! The front end writes commands to the engine's stdin, one per line, and
+//! the engine answers on its stdout. Unknown commands are ignored.
+//!
+//! | Front end                            | Engine answers                         |
+//! |--------------------------------------|----------------------------------------|
+//! | `protocol`                           | `id name <name>`, then `protocolok`    |
+//! | `isready`                            | `readyok`                              |
+//! | `newgame`                            | nothing                                |
+//! | `position <notation> [moves <m>...]` | nothing                                |
+//! | `go [movetime <ms>] [depth <plies>]` | `info ...` lines, then `bestmove <m>`  |
+//! | `quit`                               | nothing, the engine exits              |
+//!
+//! `position` takes a position in the [notation](crate::notation), followed
+//! by moves (cell indices) to play from it. Without `position` the engine
+//! uses the empty 3×3 board.
+//!
+//! `go` searches the current position for at most `movetime` milliseconds
+//! and at most `depth` plies. The engine reports
+//! `info depth <plies> score <score> pv <move>` before answering with
+//! `bestmove <move>`, or `bestmove none` if there are no moves. The score is
+//! from the point of view of the side to move: `win <plies>`,
+//! `loss <plies>`, `draw` or `cp <heuristic score>`. A `depth` of 0 is
+//! searched as 1. A `depth` or `movetime` that is not a number in range is
+//! answered with `info string <error>` and `bestmove none`.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::time::Duration;
+
+use crate::engine::{Verdict, DEFAULT_TIME_BUDGET};
+use crate::game::TicTacToe;
+
+/// Name this engine reports in answer to `protocol`.
+pub const ENGINE_NAME: &str = concat!("tic-tac-toe ", env!("CARGO_PKG_VERSION"));
+
+/// Runs this crate's engine, reading commands from `input` and writing the
+/// answers to `output`, until `quit` or the end of `input`.
+pub fn run_engine(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut tictactoe = TicTacToe::new();
+    for line in input.lines() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("protocol") => {
+                writeln!(output, "id name {}", ENGINE_NAME)?;
+                writeln!(output, "protocolok")?;
+            }
+            Some("isready") => writeln!(output, "readyok")?,
+            Some("newgame") => tictactoe = TicTacToe::new(),
+            Some("position") => {
+                let arguments = line
+                    .trim_start()
+                    .strip_prefix("position")
+                    .expect("the line starts with the command");
+                match parse_position(arguments) {
+                    Ok(position) => tictactoe = position,
+                    Err(err) => writeln!(output, "info string invalid position: {}", err)?,
+                }
+            }
+            Some("go") => {
+                let (max_depth, time_budget) = match parse_go(words) {
+                    Ok(limits) => limits,
+                    Err(err) => {
+                        writeln!(output, "info string {}", err)?;
+                        writeln!(output, "bestmove none")?;
+                        output.flush()?;
+                        continue;
+                    }
+                };
+                let result = tictactoe.search(max_depth, Some(time_budget));
+                match result.best_move {
+                    Some(mv) => {
+                        let verdict = tictactoe.verdict(result.score, result.exact);
+                        writeln!(
+                            output,
+                            "info depth {} score {} pv {}",
+                            result.depth,
+                            format_score(verdict),
+                            mv
+                        )?;
+                        writeln!(output, "bestmove {}", mv)?;
+                    }
+                    None => writeln!(output, "bestmove none")?,
+                }
+            }
+            Some("quit") => break,
+            _ => {}
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// Parses the arguments of `position`: a position and optional moves.
+fn parse_position(text: &str) -> Result<TicTacToe, String> {
+    let (notation, moves) = match text.split_once(" moves") {
+        Some((notation, moves)) => (notation, moves),
+        None => (text, ""),
+    };
+    let mut tictactoe = TicTacToe::from_notation(notation).map_err(|err| err.to_string())?;
+    for mv in moves.split_whitespace() {
+        match mv.parse() {
+            Ok(mv) if tictactoe.is_move_valid(mv) => tictactoe.make_move(mv),
+            _ => return Err(format!("illegal move '{}'", mv)),
+        }
+    }
+    Ok(tictactoe)
+}
+
+/// Parses the arguments of `go`. A depth or move time that is not a number
+/// in range is an error; unknown arguments are skipped.
+fn parse_go<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<(u32, Duration), String> {
+    let mut max_depth = u32::MAX;
+    let mut time_budget = DEFAULT_TIME_BUDGET;
+    while let Some(word) = words.next() {
+        let value = words.next().unwrap_or_default();
+        match word {
+            "depth" => {
+                max_depth = value
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|depth| u32::try_from(depth).ok())
+                    .ok_or_else(|| format!("invalid depth '{}'", value))?;
+            }
+            "movetime" => {
+                let millis = value
+                    .parse()
+                    .map_err(|_| format!("invalid movetime '{}'", value))?;
+                time_budget = Duration::from_millis(millis);
+            }
+            _ => {}
+        }
+    }
+    Ok((max_depth, time_budget))
+}
+
+fn format_score(verdict: Verdict) -> String {
+    match verdict {
+        Verdict::Win(plies) => format!("win {}", plies),
+        Verdict::Loss(plies) => format!("loss {}", plies),
+        Verdict::Draw => "draw".to_string(),
+        Verdict::Unclear(score) => format!("cp {}", score),
+    }
+}
+
+/// An engine running in another process, spoken to over the protocol.
+#[derive(Debug)]
+pub struct ExternalEngine {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    name: String,
+}
+
+impl ExternalEngine {
+    /// Starts `command` (a program followed by its arguments, separated by
+    /// whitespace) and waits for it to finish the `protocol` handshake.
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty engine command"))?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let mut engine = Self {
+            child,
+            stdin,
+            stdout,
+            name: command.to_string(),
+        };
+
+        engine.send("protocol")?;
+        while let Some(line) = engine.read_line()? {
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.to_string();
+            } else if line == "protocolok" {
+                return Ok(engine);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "engine exited during the handshake",
+        ))
+    }
+
+    /// The name the engine reported, or its command if it did not give one.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Asks the engine for its move in `tictactoe`, giving it `movetime` to
+    /// think. Returns `None` if the engine answers `bestmove none`.
+    pub fn best_move(
+        &mut self,
+        tictactoe: &TicTacToe,
+        movetime: Duration,
+    ) -> io::Result<Option<usize>> {
+        self.send(&format!("position {}", tictactoe.to_notation()))?;
+        self.send(&format!("go movetime {}", movetime.as_millis()))?;
+        while let Some(line) = self.read_line()? {
+            if let Some(mv) = line.strip_prefix("bestmove ") {
+                return match mv.trim() {
+                    "none" => Ok(None),
+                    mv => mv.parse().map(Some).map_err(|_| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("engine sent an invalid move '{}'", mv),
+                        )
+                    }),
+                };
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "engine exited before sending a move",
+        ))
+    }
+
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim_end().to_string()))
+    }
+}
+
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the engine on `input` and returns its answers.
+    fn run(input: &str) -> Vec<String> {
+        let mut output = Vec::new();
+        run_engine(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn answers_the_handshake() {
+        assert_eq!(
+            run("protocol\nisready\n"),
+            [
+                format!("id name {}", ENGINE_NAME),
+                "protocolok".to_string(),
+                "readyok".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn plays_from_a_position() {
+        assert_eq!(
+            run("position xx1/oo1/3 x 3\ngo depth 2\n"),
+            ["info depth 1 score win 1 pv 2", "bestmove 2"]
+        );
+        // The moves after the position are played before searching.
+        assert_eq!(
+            run("position 3/3/3 x 3 moves 0 3 1 4\ngo\n"),
+            ["info depth 1 score win 1 pv 2", "bestmove 2"]
+        );
+    }
+
+    #[test]
+    fn accepts_leading_whitespace() {
+        for prefix in ["  ", "\t", "\u{3000}"] {
+            let answers = run(&format!("{}position xx1/oo1/3 x 3\n{}go\n", prefix, prefix));
+            assert_eq!(answers.last().unwrap(), "bestmove 2", "{:?}", prefix);
+        }
+    }
+
+    #[test]
+    fn stops_at_quit() {
+        assert_eq!(run("isready\nquit\nisready\n"), ["readyok"]);
+    }
+
+    #[test]
+    fn searches_at_least_one_ply() {
+        assert_eq!(
+            run("go depth 0\n"),
+            ["info depth 1 score cp 4 pv 4", "bestmove 4"]
+        );
+    }
+
+    #[test]
+    fn answers_none_when_the_game_is_over() {
+        assert_eq!(run("position xxx/oo1/3 o 3\ngo\n"), ["bestmove none"]);
+    }
+
+    #[test]
+    fn reports_malformed_input() {
+        let answers = run(
+            "position 3/2 x 3\nposition 3/3/3 x 3 moves 0 0\ngo depth -1\ngo movetime soon\nfly\n",
+        );
+        assert_eq!(
+            answers,
+            [
+                "info string invalid position: row 2 has 2 cells, expected 3".to_string(),
+                "info string invalid position: illegal move '0'".to_string(),
+                "info string invalid depth '-1'".to_string(),
+                "bestmove none".to_string(),
+                "info string invalid movetime 'soon'".to_string(),
+                "bestmove none".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_position_after_an_invalid_one() {
+        assert_eq!(
+            run("position xx1/oo1/3 x 3\nposition nonsense\ngo\n")
+                .last()
+                .unwrap(),
+            "bestmove 2"
+        );
+    }
+}
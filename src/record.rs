@@ -0,0 +1,525 @@
+//! A PGN-style record of a whole game, which can be saved and loaded.
+//!
+//! A record is a list of headers followed by the moves:
+//!
+//! ```text
+//! [Event "Casual game"]
+//! [Date "2026.10.18"]
+//! [X "Human"]
+//! [O "Computer (perfect)"]
+//! [Variant "3x3/3"]
+//! [Result "1/2-1/2"]
+//!
+//! 1. 4 0 2. 8 2 3. 1 7 4. 6 3 5. 5 1/2-1/2
+//! ```
+//!
+//! `Variant` is the board width, height and win length, followed by the
+//! same rule letters as in the [notation], e.g. `3x3/3m` for a misère
+//! game. Games that do not start from an empty board with X to move also
+//! carry a `FEN` header with the start position in the notation.
+//! Moves are cell indices; move numbers and the result at the end are only
+//! there for readers and are skipped when loading. `Result` is `1-0` when X
+//! won, `0-1` when O won, `1/2-1/2` for a draw and `*` for an unfinished
+//! game. An unfinished game with `1-0` or `0-1` was forfeited by the loser,
+//! e.g. by an illegal move.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::game::{check_board, GameOver, Player, TicTacToe};
+use crate::notation;
+
+/// Why a game record could not be loaded.
+#[derive(Debug)]
+pub enum RecordError {
+    Io(io::Error),
+    /// A line starting with `[` is not a valid `[Name "value"]` header.
+    InvalidHeader(String),
+    /// The `Variant` header is not `<width>x<height>/<win length>`, or
+    /// describes an unsupported board.
+    InvalidVariant(String),
+    /// The `FEN` header is not a valid position.
+    InvalidPosition(notation::ParseError),
+    /// The `FEN` header does not match the `Variant` header.
+    VariantMismatch,
+    /// Move number `index` (counting from 1) is not a cell index.
+    InvalidMove {
+        index: usize,
+        text: String,
+    },
+    /// Move number `index` (counting from 1) is not legal in its position.
+    IllegalMove {
+        index: usize,
+        mv: usize,
+    },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordError::Io(err) => write!(f, "{}", err),
+            RecordError::InvalidHeader(line) => write!(f, "invalid header '{}'", line),
+            RecordError::InvalidVariant(variant) => write!(f, "invalid variant '{}'", variant),
+            RecordError::InvalidPosition(err) => write!(f, "invalid FEN header: {}", err),
+            RecordError::VariantMismatch => {
+                write!(f, "the FEN header does not match the Variant header")
+            }
+            RecordError::InvalidMove { index, text } => {
+                write!(f, "move {} '{}' is not a cell index", index, text)
+            }
+            RecordError::IllegalMove { index, mv } => {
+                write!(f, "move {} ({}) is not legal", index, mv)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<io::Error> for RecordError {
+    fn from(err: io::Error) -> Self {
+        RecordError::Io(err)
+    }
+}
+
+/// The headers, start position and moves of a game.
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    headers: Vec<(String, String)>,
+    start: TicTacToe,
+    moves: Vec<usize>,
+    /// The player who forfeited the game before it was over.
+    forfeit: Option<Player>,
+}
+
+impl GameRecord {
+    /// An empty record of a game starting from `start`, dated today.
+    pub fn new(start: &TicTacToe) -> Self {
+        let mut record = Self {
+            headers: Vec::new(),
+            start: start.clone(),
+            moves: Vec::new(),
+            forfeit: None,
+        };
+        record.set_header("Event", "Casual game");
+        record.set_header("Date", &today());
+        record.set_header("X", "?");
+        record.set_header("O", "?");
+        record
+    }
+
+    /// The value of header `name`, if it is set.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets header `name`, keeping its place if it already exists. `Variant`,
+    /// `FEN` and `Result` are always derived from the game itself.
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|(header, _)| header == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.headers.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// The position the game started from.
+    pub fn start(&self) -> &TicTacToe {
+        &self.start
+    }
+
+    /// Replaces the symbols the start position, and so every position
+    /// replayed from the record, is drawn with.
+    pub fn set_symbols(&mut self, player_1: char, player_2: char) {
+        self.start = self.start.clone().with_symbols(player_1, player_2);
+    }
+
+    pub fn moves(&self) -> &[usize] {
+        &self.moves
+    }
+
+    /// Appends a move. It is not checked until the record is replayed.
+    pub fn push(&mut self, mv: usize) {
+        self.moves.push(mv);
+    }
+
+    /// Replaces all moves. They are not checked until the record is replayed.
+    pub fn set_moves(&mut self, moves: &[usize]) {
+        self.moves = moves.to_vec();
+    }
+
+    /// Records that `loser` forfeited the game, so the other player wins it
+    /// unless the moves already finish the game.
+    pub fn set_forfeit(&mut self, loser: Player) {
+        self.forfeit = Some(loser);
+    }
+
+    /// Plays the first `count` moves from the start position.
+    pub fn position_after(&self, count: usize) -> Result<TicTacToe, RecordError> {
+        let mut tictactoe = self.start.clone();
+        for (index, &mv) in self.moves.iter().take(count).enumerate() {
+            if !tictactoe.is_move_valid(mv) || tictactoe.game_over() != GameOver::OnGoing {
+                return Err(RecordError::IllegalMove {
+                    index: index + 1,
+                    mv,
+                });
+            }
+            tictactoe.make_move(mv);
+        }
+        Ok(tictactoe)
+    }
+
+    /// Plays all moves from the start position and returns the final one.
+    pub fn replay(&self) -> Result<TicTacToe, RecordError> {
+        self.position_after(self.moves.len())
+    }
+
+    /// The `Result` header value for the game so far.
+    pub fn result(&self) -> &'static str {
+        match self.replay().map(|tictactoe| tictactoe.game_over()) {
+            Ok(GameOver::Winner(Player::One)) => "1-0",
+            Ok(GameOver::Winner(Player::Two)) => "0-1",
+            Ok(GameOver::Draw) => "1/2-1/2",
+            Ok(GameOver::OnGoing) => match self.forfeit {
+                Some(Player::Two) => "1-0",
+                Some(Player::One) => "0-1",
+                None => "*",
+            },
+            Err(_) => "*",
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RecordError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    fn is_standard_start(&self) -> bool {
+        self.start.move_count() == 0
+            && self.start.turn_to_move() == Player::One
+            && self.start.get_all_moves().len() == self.start.size()
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in &self.headers {
+            if !matches!(name.as_str(), "Variant" | "FEN" | "Result") {
+                writeln!(f, "[{} \"{}\"]", name, escape(value))?;
+            }
+        }
+        writeln!(
+            f,
+            "[Variant \"{}x{}/{}{}\"]",
+            self.start.width(),
+            self.start.height(),
+            self.start.win_length(),
+            notation::rules_suffix(&self.start)
+        )?;
+        if !self.is_standard_start() {
+            writeln!(f, "[FEN \"{}\"]", self.start.to_notation())?;
+        }
+        let result = self.result();
+        writeln!(f, "[Result \"{}\"]", result)?;
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        for (index, mv) in self.moves.iter().enumerate() {
+            if index % 2 == 0 {
+                tokens.push(format!("{}.", index / 2 + 1));
+            }
+            tokens.push(mv.to_string());
+        }
+        tokens.push(result.to_string());
+        writeln!(f, "{}", tokens.join(" "))
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut headers = Vec::new();
+        let mut move_text = String::new();
+        for line in text.lines().map(str::trim) {
+            if line.starts_with('[') {
+                headers.push(parse_header(line)?);
+            } else {
+                move_text.push_str(line);
+                move_text.push(' ');
+            }
+        }
+        let find = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _): &&(String, String)| header == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let (width, height, win_length, misere, exact_length) = match find("Variant") {
+            Some(variant) => {
+                let (variant, misere, exact_length) = notation::split_rules(variant);
+                let (width, height, win_length) = parse_variant(variant)?;
+                (width, height, win_length, misere, exact_length)
+            }
+            None => (3, 3, 3, false, false),
+        };
+        let start = match find("FEN") {
+            Some(fen) => {
+                let start = TicTacToe::from_notation(fen).map_err(RecordError::InvalidPosition)?;
+                let rules = (start.is_misere(), start.is_exact_length());
+                if (start.width(), start.height(), start.win_length(), rules)
+                    != (width, height, win_length, (misere, exact_length))
+                {
+                    return Err(RecordError::VariantMismatch);
+                }
+                start
+            }
+            None => TicTacToe::with_size(width, height, win_length)
+                .with_misere(misere)
+                .with_exact_length(exact_length),
+        };
+
+        let mut moves = Vec::new();
+        for token in move_text.split_whitespace() {
+            if token.ends_with('.') || matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+                continue;
+            }
+            let mv = token.parse().map_err(|_| RecordError::InvalidMove {
+                index: moves.len() + 1,
+                text: token.to_string(),
+            })?;
+            moves.push(mv);
+        }
+
+        let forfeit = match find("Result") {
+            Some("1-0") => Some(Player::Two),
+            Some("0-1") => Some(Player::One),
+            _ => None,
+        };
+        headers.retain(|(name, _)| !matches!(name.as_str(), "Variant" | "FEN" | "Result"));
+        let record = Self {
+            headers,
+            start,
+            moves,
+            forfeit,
+        };
+        record.replay()?;
+        Ok(record)
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn parse_header(line: &str) -> Result<(String, String), RecordError> {
+    let invalid = || RecordError::InvalidHeader(line.to_string());
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .ok_or_else(invalid)?;
+    let (name, quoted) = inner.split_once(' ').ok_or_else(invalid)?;
+    let value = quoted
+        .trim()
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        unescaped.push(if c == '\\' {
+            chars.next().ok_or_else(invalid)?
+        } else {
+            c
+        });
+    }
+    Ok((name.to_string(), unescaped))
+}
+
+/// Parses a `Variant` header value, `<width>x<height>/<win length>`, and
+/// checks the board can be played (see [`check_board`]).
+pub fn parse_variant(variant: &str) -> Result<(usize, usize, usize), RecordError> {
+    let invalid = || RecordError::InvalidVariant(variant.to_string());
+    let (size, win_length) = variant.split_once('/').ok_or_else(invalid)?;
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    let numbers: Vec<usize> = [width, height, win_length]
+        .iter()
+        .map(|n| n.parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    check_board(numbers[0], numbers[1], numbers[2]).map_err(|_| invalid())
+}
+
+/// Today's date as `YYYY.MM.DD`, in UTC.
+fn today() -> String {
+    let days = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86_400)
+        .unwrap_or_default() as i64;
+    // Converts days since 1970-01-01 to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(moves: &[usize]) -> GameRecord {
+        let mut record = GameRecord::new(&TicTacToe::new());
+        record.set_header("X", "Alice");
+        record.set_header("O", "Bob \"the \\ builder\"");
+        record.set_moves(moves);
+        record
+    }
+
+    #[test]
+    fn round_trips() {
+        let record = record(&[4, 0, 2, 8, 6]);
+        let text = record.to_string();
+        let parsed: GameRecord = text.parse().unwrap();
+        assert_eq!(parsed.moves(), record.moves());
+        assert_eq!(parsed.header("X"), Some("Alice"));
+        assert_eq!(parsed.header("O"), Some("Bob \"the \\ builder\""));
+        assert_eq!(parsed.result(), "1-0");
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn round_trips_variants_and_start_positions() {
+        let misere = TicTacToe::with_size(4, 3, 3).with_misere(true);
+        let mut record = GameRecord::new(&misere);
+        record.set_moves(&[0, 5]);
+        let text = record.to_string();
+        assert!(text.contains("[Variant \"4x3/3m\"]"));
+        let parsed: GameRecord = text.parse().unwrap();
+        assert!(parsed.start().is_misere());
+        assert_eq!(parsed.to_string(), text);
+
+        let start = TicTacToe::from_notation("x2/1o1/3 x 3").unwrap();
+        let mut record = GameRecord::new(&start);
+        record.push(8);
+        let text = record.to_string();
+        assert!(text.contains("[FEN \"x2/1o1/3 x 3 2\"]"));
+        let parsed: GameRecord = text.parse().unwrap();
+        assert_eq!(parsed.start().to_notation(), start.to_notation());
+        assert_eq!(parsed.moves(), [8]);
+    }
+
+    #[test]
+    fn reads_results_and_move_numbers() {
+        let parsed: GameRecord = "1. 4 0 2. 8 *".parse().unwrap();
+        assert_eq!(parsed.moves(), [4, 0, 8]);
+        assert_eq!(parsed.result(), "*");
+        assert_eq!(parsed.header("Variant"), None);
+    }
+
+    #[test]
+    fn round_trips_forfeits() {
+        let mut forfeited = record(&[4, 0]);
+        forfeited.set_forfeit(Player::One);
+        assert_eq!(forfeited.result(), "0-1");
+        let parsed: GameRecord = forfeited.to_string().parse().unwrap();
+        assert_eq!(parsed.result(), "0-1");
+
+        // A finished game keeps the result of its moves.
+        let mut forfeited = record(&[4, 0, 2, 8, 6]);
+        forfeited.set_forfeit(Player::One);
+        assert_eq!(forfeited.result(), "1-0");
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        for header in [
+            "[Event \"unterminated]",
+            "[Event]",
+            "[Event \"value\"",
+            "[Event value]",
+            "[Event \"trailing\\\"]",
+        ] {
+            assert!(
+                matches!(
+                    header.parse::<GameRecord>(),
+                    Err(RecordError::InvalidHeader(_))
+                ),
+                "{}",
+                header
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_variants() {
+        for variant in [
+            "3x3",
+            "3/3",
+            "axb/c",
+            "0x3/3",
+            "3x3/0",
+            "3x3/4",
+            "5x1/6",
+            "17x16/5",
+            "4294967296x4294967296/3",
+            "18446744073709551615x2/3",
+        ] {
+            let text = format!("[Variant \"{}\"]\n", variant);
+            assert!(
+                matches!(
+                    text.parse::<GameRecord>(),
+                    Err(RecordError::InvalidVariant(_))
+                ),
+                "{}",
+                variant
+            );
+        }
+        assert_eq!(parse_variant("16x16/5").unwrap(), (16, 16, 5));
+    }
+
+    #[test]
+    fn rejects_a_fen_that_does_not_match_the_variant() {
+        let text = "[Variant \"4x4/3\"]\n[FEN \"3/3/3 x 3\"]\n";
+        assert!(matches!(
+            text.parse::<GameRecord>(),
+            Err(RecordError::VariantMismatch)
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_moves() {
+        assert!(matches!(
+            "1. 4 zero".parse::<GameRecord>(),
+            Err(RecordError::InvalidMove { index: 2, .. })
+        ));
+        assert!(matches!(
+            "1. 4 4".parse::<GameRecord>(),
+            Err(RecordError::IllegalMove { index: 2, mv: 4 })
+        ));
+        assert!(matches!(
+            "1. 4 9".parse::<GameRecord>(),
+            Err(RecordError::IllegalMove { index: 2, mv: 9 })
+        ));
+        // Moves after the game is over are not legal either.
+        assert!(matches!(
+            "1. 0 3 2. 1 4 3. 2 5".parse::<GameRecord>(),
+            Err(RecordError::IllegalMove { index: 6, mv: 5 })
+        ));
+    }
+}
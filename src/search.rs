@@ -0,0 +1,284 @@
+//! Alpha-beta search with a transposition table and iterative deepening,
+//! shared by every game the computer plays.
+//!
+//! A game takes part by implementing [`Position`]. Scores follow the
+//! [engine](crate::engine)'s convention: they are from [`Player::One`]'s
+//! point of view, and a won game scores [`WIN_SCORE`] minus the number of
+//! moves played.
+
+use std::time::{Duration, Instant};
+
+use crate::bitboard::MAX_CELLS;
+use crate::engine::WIN_SCORE;
+use crate::game::Player;
+use crate::transposition::{Bound, Entry, TranspositionTable};
+
+/// Scores at least this far from zero mean the game is decided.
+pub(crate) const DECIDED_SCORE: i32 = WIN_SCORE - MAX_CELLS as i32;
+
+/// A game position the search can walk through by playing moves and taking
+/// them back.
+pub trait Position {
+    /// How many nodes are searched between two looks at the clock.
+    const NODES_PER_TIME_CHECK: u64 = 1024;
+
+    fn to_move(&self) -> Player;
+
+    /// Whether the game is won or drawn.
+    fn is_over(&self) -> bool;
+
+    /// The moves worth searching, most promising first. Usually every legal
+    /// move, but big boards may leave out the hopeless ones.
+    fn search_moves(&self) -> Vec<usize>;
+
+    /// Plays `mv`, one of [`search_moves`](Self::search_moves).
+    fn play(&mut self, mv: usize);
+
+    /// Takes back `mv`, the last move played.
+    fn take_back(&mut self, mv: usize);
+
+    /// Scores the position, exactly for finished games and heuristically
+    /// otherwise.
+    fn evaluate(&self) -> i32;
+
+    /// A score known without searching, such as a win nobody can stop any
+    /// more. Such positions are not searched further.
+    fn proven_score(&self) -> Option<i32> {
+        None
+    }
+
+    /// The key the position is stored under in the transposition table, or
+    /// `None` to not store it.
+    fn key(&self) -> Option<u64> {
+        None
+    }
+
+    /// The most moves the game can still last, which is as deep as there is
+    /// any point in searching.
+    fn plies_left(&self) -> u32;
+}
+
+/// Outcome of [`search`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchResult {
+    /// The best move found, or `None` if there are no moves.
+    pub best_move: Option<usize>,
+    /// Score of the position after `best_move`, see the module docs and
+    /// [`TicTacToe::plies_to_win`](crate::TicTacToe::plies_to_win).
+    pub score: i32,
+    /// Depth of the deepest fully completed iteration.
+    pub depth: u32,
+    /// Whether that iteration reached the end of the game on every line,
+    /// so `score` is not a heuristic guess.
+    pub exact: bool,
+}
+
+/// State shared by all nodes of one search.
+struct SearchContext<'a> {
+    table: &'a mut TranspositionTable,
+    deadline: Option<Instant>,
+    nodes_per_time_check: u64,
+    nodes: u64,
+    aborted: bool,
+}
+
+impl SearchContext<'_> {
+    fn out_of_time(&mut self) -> bool {
+        self.nodes += 1;
+        if !self.aborted && self.nodes.is_multiple_of(self.nodes_per_time_check) {
+            if let Some(deadline) = self.deadline {
+                self.aborted = Instant::now() >= deadline;
+            }
+        }
+        self.aborted
+    }
+}
+
+/// Iterative deepening: searches one ply deeper each iteration, up to
+/// `max_depth` plies, until `time_budget` runs out or the game is decided.
+/// The result of the last completed iteration is returned, and a depth-1
+/// search is always finished so there is a move to play, even if
+/// `max_depth` is 0.
+pub fn search<P: Position>(
+    position: &mut P,
+    max_depth: u32,
+    time_budget: Option<Duration>,
+) -> SearchResult {
+    let mut table = TranspositionTable::new();
+    let mut context = SearchContext {
+        table: &mut table,
+        deadline: time_budget.map(|budget| Instant::now() + budget),
+        nodes_per_time_check: P::NODES_PER_TIME_CHECK,
+        nodes: 0,
+        aborted: false,
+    };
+    let mut moves = if position.is_over() {
+        Vec::new()
+    } else {
+        position.search_moves()
+    };
+    let plies_left = position.plies_left();
+    let max_depth = if moves.is_empty() {
+        0
+    } else {
+        max_depth.max(1).min(plies_left)
+    };
+    let mut result = SearchResult {
+        best_move: None,
+        score: position.evaluate(),
+        depth: 0,
+        exact: moves.is_empty(),
+    };
+
+    for depth in 1..=max_depth {
+        let Some((best_move, score)) = search_root(position, &mut context, &moves, depth) else {
+            break;
+        };
+        result = SearchResult {
+            best_move: Some(best_move),
+            score,
+            depth,
+            exact: depth >= plies_left,
+        };
+        if score.abs() >= DECIDED_SCORE {
+            break;
+        }
+        // Try the best move first next time, it makes the cutoffs cheaper.
+        moves.retain(|&mv| mv != best_move);
+        moves.insert(0, best_move);
+    }
+
+    result
+}
+
+/// Searches `position` `depth` plies deep and returns its score within the
+/// `alpha`..`beta` window. Results are stored in `table` under the
+/// position's [key](Position::key) and reused by later searches.
+pub fn alpha_beta<P: Position>(
+    position: &mut P,
+    table: &mut TranspositionTable,
+    depth: u32,
+    alpha: i32,
+    beta: i32,
+) -> i32 {
+    let mut context = SearchContext {
+        table,
+        deadline: None,
+        nodes_per_time_check: P::NODES_PER_TIME_CHECK,
+        nodes: 0,
+        aborted: false,
+    };
+    search_node(position, &mut context, depth, alpha, beta)
+}
+
+/// Searches every root move to `depth`, or returns `None` if the search
+/// ran out of time. The first iteration is never cut short.
+fn search_root<P: Position>(
+    position: &mut P,
+    context: &mut SearchContext,
+    moves: &[usize],
+    depth: u32,
+) -> Option<(usize, i32)> {
+    let maximizing = position.to_move() == Player::One;
+    let mut best: Option<(usize, i32)> = None;
+    for &mv in moves {
+        position.play(mv);
+        // Only a move that beats the best one so far matters, so the
+        // window starts at the best score found.
+        let eval = match best {
+            Some((_, score)) if maximizing => {
+                search_node(position, context, depth - 1, score, i32::MAX)
+            }
+            Some((_, score)) => search_node(position, context, depth - 1, i32::MIN, score),
+            None => search_node(position, context, depth - 1, i32::MIN, i32::MAX),
+        };
+        position.take_back(mv);
+        if context.aborted && depth > 1 {
+            return None;
+        }
+        let better = match best {
+            None => true,
+            Some((_, score)) if maximizing => eval > score,
+            Some((_, score)) => eval < score,
+        };
+        if better {
+            best = Some((mv, eval));
+        }
+    }
+    best
+}
+
+fn search_node<P: Position>(
+    position: &mut P,
+    context: &mut SearchContext,
+    depth: u32,
+    mut alpha: i32,
+    mut beta: i32,
+) -> i32 {
+    let out_of_time = context.out_of_time();
+    if depth == 0 || position.is_over() {
+        return position.evaluate();
+    }
+    if let Some(score) = position.proven_score() {
+        return score;
+    }
+    if out_of_time {
+        return 0;
+    }
+
+    let key = position.key();
+    if let Some(entry) = key.and_then(|key| context.table.get(key)) {
+        if entry.depth >= depth {
+            match entry.bound {
+                Bound::Exact => return entry.score,
+                Bound::Lower => alpha = std::cmp::max(alpha, entry.score),
+                Bound::Upper => beta = std::cmp::min(beta, entry.score),
+            }
+            if beta <= alpha {
+                return entry.score;
+            }
+        }
+    }
+    let (alpha_searched, beta_searched) = (alpha, beta);
+
+    let maximizing = position.to_move() == Player::One;
+    let mut best = if maximizing { i32::MIN } else { i32::MAX };
+    for mv in position.search_moves() {
+        position.play(mv);
+        let eval = search_node(position, context, depth - 1, alpha, beta);
+        position.take_back(mv);
+
+        if maximizing {
+            best = std::cmp::max(best, eval);
+            alpha = std::cmp::max(alpha, eval);
+        } else {
+            best = std::cmp::min(best, eval);
+            beta = std::cmp::min(beta, eval);
+        }
+        if beta <= alpha || context.aborted {
+            break;
+        }
+    }
+    if context.aborted {
+        return 0;
+    }
+
+    let bound = if best <= alpha_searched {
+        Bound::Upper
+    } else if best >= beta_searched {
+        Bound::Lower
+    } else {
+        Bound::Exact
+    };
+    if let Some(key) = key {
+        context.table.insert(
+            key,
+            Entry {
+                score: best,
+                bound,
+                depth,
+            },
+        );
+    }
+    best
+}
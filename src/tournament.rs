@@ -0,0 +1,279 @@
+//! Matches between two computer players, built-in or external engines.
+
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use crate::engine::Difficulty;
+use crate::game::{GameOver, Player, TicTacToe};
+use crate::protocol::ExternalEngine;
+use crate::record::GameRecord;
+use crate::rng::Rng;
+
+/// Two-sided 95% quantile of the normal distribution.
+const Z_95: f64 = 1.96;
+
+/// One of the two players of a match.
+#[derive(Debug)]
+pub enum Contestant {
+    /// This crate's engine at the given level.
+    Computer(Difficulty),
+    /// An engine in another process, given `movetime` per move.
+    Engine {
+        engine: ExternalEngine,
+        movetime: Duration,
+    },
+}
+
+impl Contestant {
+    pub fn name(&self) -> String {
+        match self {
+            Contestant::Computer(level) => format!("Computer ({})", level),
+            Contestant::Engine { engine, .. } => engine.name().to_string(),
+        }
+    }
+
+    /// The contestant's move in `tictactoe`, or `None` if it has none.
+    fn pick_move(&mut self, tictactoe: &mut TicTacToe, rng: &mut Rng) -> io::Result<Option<usize>> {
+        match self {
+            Contestant::Computer(level) => Ok(tictactoe.computer_move(*level, rng)),
+            Contestant::Engine { engine, movetime } => engine.best_move(tictactoe, *movetime),
+        }
+    }
+}
+
+/// Wins, draws and losses of the first contestant of a match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MatchResult {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchResult {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points per game, counting a win as 1 and a draw as ½.
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// 95% confidence interval of [`score`](Self::score). This is the
+    /// Wilson interval of a win rate, which is a little too wide when there
+    /// are draws but stays sensible for lopsided matches.
+    pub fn score_interval(&self) -> (f64, f64) {
+        wilson_interval(self.score(), self.games())
+    }
+
+    /// 95% confidence interval of the share of games with `count` of them,
+    /// e.g. `rate_interval(result.wins)`.
+    pub fn rate_interval(&self, count: u32) -> (f64, f64) {
+        let games = self.games();
+        wilson_interval(count as f64 / games.max(1) as f64, games)
+    }
+
+    /// Elo difference matching `score`, or `None` for a score of 0 or 1,
+    /// where it would be infinite.
+    pub fn elo(score: f64) -> Option<f64> {
+        (score > 0.0 && score < 1.0).then(|| -400.0 * (1.0 / score - 1.0).log10())
+    }
+}
+
+impl fmt::Display for MatchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = |(low, high): (f64, f64)| format!("{:.1}%-{:.1}%", low * 100.0, high * 100.0);
+        writeln!(f, "Games:  {}", self.games())?;
+        for (name, count) in [
+            ("Wins:  ", self.wins),
+            ("Draws: ", self.draws),
+            ("Losses:", self.losses),
+        ] {
+            writeln!(
+                f,
+                "{} {} (95% CI {})",
+                name,
+                count,
+                percent(self.rate_interval(count))
+            )?;
+        }
+        let (low, high) = self.score_interval();
+        writeln!(
+            f,
+            "Score:  {:.1}% (95% CI {})",
+            self.score() * 100.0,
+            percent((low, high))
+        )?;
+        let elo = |score| match MatchResult::elo(score) {
+            Some(elo) => format!("{:+.0}", elo),
+            None => "n/a".to_string(),
+        };
+        write!(
+            f,
+            "Elo:    {} (95% CI {} to {})",
+            elo(self.score()),
+            elo(low),
+            elo(high)
+        )
+    }
+}
+
+/// Plays `games` games between `contestants` from `start`, swapping sides
+/// after every game so both start equally often. The result is from the
+/// first contestant's point of view. `on_game` is called with the record of
+/// every finished game and the result so far. A contestant without a legal
+/// move loses the game.
+pub fn play_match(
+    contestants: &mut [Contestant; 2],
+    start: &TicTacToe,
+    games: u32,
+    rng: &mut Rng,
+    mut on_game: impl FnMut(&GameRecord, &MatchResult),
+) -> io::Result<MatchResult> {
+    let mut result = MatchResult::default();
+    for game in 0..games {
+        // The contestant playing the side to move in `start`.
+        let first = game as usize % 2;
+        let seat = |player: Player| {
+            if player == start.turn_to_move() {
+                first
+            } else {
+                1 - first
+            }
+        };
+
+        let mut record = GameRecord::new(start);
+        record.set_header("Event", &format!("Match game {}", game + 1));
+        record.set_header("X", &contestants[seat(Player::One)].name());
+        record.set_header("O", &contestants[seat(Player::Two)].name());
+        let mut tictactoe = start.clone();
+        let winner = loop {
+            match tictactoe.game_over() {
+                GameOver::Winner(player) => break Some(seat(player)),
+                GameOver::Draw => break None,
+                GameOver::OnGoing => {}
+            }
+            let mover = seat(tictactoe.turn_to_move());
+            match contestants[mover].pick_move(&mut tictactoe, rng)? {
+                Some(mv) if tictactoe.is_move_valid(mv) => {
+                    tictactoe.make_move(mv);
+                    record.push(mv);
+                }
+                _ => {
+                    record.set_header("Termination", "illegal move");
+                    record.set_forfeit(tictactoe.turn_to_move());
+                    break Some(1 - mover);
+                }
+            }
+        };
+
+        match winner {
+            Some(0) => result.wins += 1,
+            Some(_) => result.losses += 1,
+            None => result.draws += 1,
+        }
+        on_game(&record, &result);
+    }
+    Ok(result)
+}
+
+/// 95% Wilson score interval of a rate observed over `games` games.
+fn wilson_interval(rate: f64, games: u32) -> (f64, f64) {
+    if games == 0 {
+        return (0.0, 1.0);
+    }
+    let games = games as f64;
+    let z2 = Z_95 * Z_95;
+    let center = (rate + z2 / (2.0 * games)) / (1.0 + z2 / games);
+    let margin = Z_95 / (1.0 + z2 / games)
+        * (rate * (1.0 - rate) / games + z2 / (4.0 * games * games)).sqrt();
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wilson_interval_stays_within_bounds() {
+        assert_eq!(wilson_interval(0.5, 0), (0.0, 1.0));
+        for games in [1, 10, 1000] {
+            for rate in [0.0, 0.1, 0.5, 0.9, 1.0] {
+                let (low, high) = wilson_interval(rate, games);
+                assert!(
+                    (0.0..=rate).contains(&low) && (rate..=1.0).contains(&high),
+                    "{} of {} games: {}-{}",
+                    rate,
+                    games,
+                    low,
+                    high
+                );
+            }
+        }
+        let (low, high) = wilson_interval(0.5, 100);
+        assert!((low - 0.404).abs() < 0.001 && (high - 0.596).abs() < 0.001);
+        // More games narrow the interval.
+        let narrow = wilson_interval(0.5, 1000);
+        assert!(narrow.0 > low && narrow.1 < high);
+    }
+
+    #[test]
+    fn elo_is_finite_or_absent() {
+        assert_eq!(MatchResult::elo(0.5), Some(0.0));
+        assert!((MatchResult::elo(0.75).unwrap() - 190.8).abs() < 0.1);
+        assert!((MatchResult::elo(0.25).unwrap() + 190.8).abs() < 0.1);
+        assert_eq!(MatchResult::elo(0.0), None);
+        assert_eq!(MatchResult::elo(1.0), None);
+
+        let shutout = MatchResult {
+            wins: 10,
+            draws: 0,
+            losses: 0,
+        };
+        let report = shutout.to_string();
+        assert!(report.contains("Elo:    n/a (95% CI +"), "{}", report);
+        assert!(!report.contains("inf"), "{}", report);
+    }
+
+    #[test]
+    fn plays_a_match() {
+        let mut contestants = [
+            Contestant::Computer(Difficulty::Perfect),
+            Contestant::Computer(Difficulty::Random),
+        ];
+        let mut firsts = Vec::new();
+        let mut reported = Vec::new();
+        let result = play_match(
+            &mut contestants,
+            &TicTacToe::new(),
+            4,
+            &mut Rng::new(1),
+            |record, result| {
+                firsts.push(record.header("X").unwrap().to_string());
+                reported.push(*result);
+            },
+        )
+        .unwrap();
+
+        assert_eq!(result.games(), 4);
+        assert_eq!(result.losses, 0);
+        assert_eq!(reported.len(), 4);
+        assert_eq!(*reported.last().unwrap(), result);
+        for (index, partial) in reported.iter().enumerate() {
+            assert_eq!(partial.games(), index as u32 + 1);
+        }
+        assert_eq!(
+            firsts,
+            [
+                "Computer (perfect)",
+                "Computer (random)",
+                "Computer (perfect)",
+                "Computer (random)"
+            ]
+        );
+    }
+}
@@ -0,0 +1,57 @@
+//! Transposition table used by the engine to remember searched positions.
+
+use std::collections::HashMap;
+
+/// How a stored score relates to the real value of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is the exact value.
+    Exact,
+    /// The real value is at least the score (the search failed high).
+    Lower,
+    /// The real value is at most the score (the search failed low).
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub score: i32,
+    pub bound: Bound,
+    /// How many plies deep the position was searched. The entry only
+    /// answers searches that are at most this deep.
+    pub depth: u32,
+}
+
+/// Search results keyed by [`TicTacToe::canonical_hash`](crate::TicTacToe::canonical_hash),
+/// so all symmetric versions of a position share one entry.
+#[derive(Debug, Clone, Default)]
+pub struct TranspositionTable {
+    entries: HashMap<u64, Entry>,
+}
+
+impl TranspositionTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: u64) -> Option<Entry> {
+        self.entries.get(&key).copied()
+    }
+
+    pub fn insert(&mut self, key: u64, entry: Entry) {
+        self.entries.insert(key, entry);
+    }
+
+    /// Number of stored positions.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,485 @@
+//! Ultimate tic-tac-toe: nine 3×3 boards arranged in a 3×3 grid.
+//!
+//! Winning a small board claims its cell of the big "meta" board, and three
+//! claimed cells in a row win the game. The cell a move is played in sends
+//! the opponent to the small board at the same position; if that board is
+//! already won or full, they may play on any open board. The game is drawn
+//! when every small board is decided and nobody has three in a row.
+//!
+//! Moves are numbered `board * 9 + cell`, with boards and cells both
+//! counted row by row from 0 to 8. Each small board and the meta board are
+//! [`TicTacToe`]s, so their wins and draws come from
+//! [`TicTacToe::game_over`].
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::bitboard::Bitboard;
+use crate::engine::{
+    Difficulty, DEFAULT_TIME_BUDGET, EASY_DEPTH, MEDIUM_BLUNDER_PERCENT, WIN_SCORE,
+};
+use crate::game::{Cell, GameOver, Player, TicTacToe};
+use crate::rng::Rng;
+use crate::search::{self, Position};
+
+/// Number of small boards, and of cells on each of them.
+pub const BOARDS: usize = 9;
+
+/// Heuristic value of a won small board, before weighting by its place on
+/// the meta board.
+const BOARD_WEIGHT: i32 = 100;
+
+/// How much a small board's place on the meta board is worth: the centre
+/// is on four lines, corners on three and edges on two.
+const PLACE_WEIGHTS: [i32; BOARDS] = [3, 2, 3, 2, 4, 2, 3, 2, 3];
+
+#[derive(Debug, Clone)]
+pub struct Ultimate {
+    boards: Vec<TicTacToe>,
+    /// Small boards won by each player.
+    meta: TicTacToe,
+    side_to_move: Player,
+    /// The small board the next move must be played on, if it is forced.
+    forced: Option<usize>,
+    /// Every move played, with the board that was forced before it.
+    history: Vec<(usize, Option<usize>)>,
+}
+
+impl Ultimate {
+    pub fn new() -> Self {
+        Self {
+            boards: vec![TicTacToe::new(); BOARDS],
+            meta: TicTacToe::new(),
+            side_to_move: Player::One,
+            forced: None,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn with_first_player(mut self, player: Player) -> Self {
+        self.side_to_move = player;
+        self
+    }
+
+    /// Sets the symbols drawn for the two players.
+    pub fn with_symbols(mut self, player_1: char, player_2: char) -> Self {
+        self.meta = self.meta.with_symbols(player_1, player_2);
+        self
+    }
+
+    pub fn turn_to_move(&self) -> Player {
+        self.side_to_move
+    }
+
+    pub fn move_count(&self) -> usize {
+        self.history.len()
+    }
+
+    /// The small board the next move must be played on, or `None` if any
+    /// open board will do.
+    pub fn forced_board(&self) -> Option<usize> {
+        self.forced
+    }
+
+    /// Small board `board`, numbered row by row.
+    pub fn board(&self, board: usize) -> &TicTacToe {
+        &self.boards[board]
+    }
+
+    /// The meta board, with a mark on every small board that was won.
+    pub fn meta(&self) -> &TicTacToe {
+        &self.meta
+    }
+
+    pub fn cell(&self, mv: usize) -> Cell {
+        self.boards[mv / BOARDS].cell(mv % BOARDS)
+    }
+
+    /// Whether small board `board` is won or full.
+    pub fn is_board_decided(&self, board: usize) -> bool {
+        self.boards[board].game_over() != GameOver::OnGoing
+    }
+
+    pub fn is_move_valid(&self, mv: usize) -> bool {
+        let board = mv / BOARDS;
+        mv < BOARDS * BOARDS
+            && self.game_over() == GameOver::OnGoing
+            && self.forced.is_none_or(|forced| forced == board)
+            && !self.is_board_decided(board)
+            && self.boards[board].is_move_valid(mv % BOARDS)
+    }
+
+    /// Plays `mv` for the side to move. The move must be valid.
+    pub fn make_move(&mut self, mv: usize) {
+        let (board, cell) = (mv / BOARDS, mv % BOARDS);
+        let player = self.side_to_move;
+        self.boards[board].place(cell, player);
+        if self.boards[board].game_over() == GameOver::Winner(player) {
+            self.meta.place(board, player);
+        }
+        self.history.push((mv, self.forced));
+        self.forced = if self.is_board_decided(cell) {
+            None
+        } else {
+            Some(cell)
+        };
+        self.side_to_move = player.opponent();
+    }
+
+    /// Takes back the last move and returns it.
+    pub fn undo_move(&mut self) -> Option<usize> {
+        let (mv, forced) = self.history.pop()?;
+        let board = mv / BOARDS;
+        // A board takes no moves once it is won, so the last move on a won
+        // board is the one that won it.
+        self.meta.remove(board);
+        self.boards[board].remove(mv % BOARDS);
+        self.forced = forced;
+        self.side_to_move = self.side_to_move.opponent();
+        Some(mv)
+    }
+
+    /// The moves played so far.
+    pub fn moves(&self) -> Vec<usize> {
+        self.history.iter().map(|&(mv, _)| mv).collect()
+    }
+
+    pub fn game_over(&self) -> GameOver {
+        match self.meta.game_over() {
+            GameOver::Winner(player) => GameOver::Winner(player),
+            _ if (0..BOARDS).all(|board| self.is_board_decided(board)) => GameOver::Draw,
+            _ => GameOver::OnGoing,
+        }
+    }
+
+    /// All legal moves, in index order.
+    pub fn get_all_moves(&self) -> Vec<usize> {
+        if self.game_over() != GameOver::OnGoing {
+            return Vec::new();
+        }
+        let boards = match self.forced {
+            Some(board) => board..board + 1,
+            None => 0..BOARDS,
+        };
+        boards
+            .filter(|&board| !self.is_board_decided(board))
+            .flat_map(|board| {
+                self.boards[board]
+                    .get_all_moves()
+                    .into_iter()
+                    .map(move |cell| board * BOARDS + cell)
+            })
+            .collect()
+    }
+
+    /// Parses a move typed as `<board> <cell>`, or just `<cell>` when the
+    /// board is forced.
+    pub fn parse_move(&self, text: &str) -> Option<usize> {
+        let numbers: Vec<usize> = text
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|part| !part.is_empty())
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()?;
+        match (&numbers[..], self.forced) {
+            (&[board, cell], _) if board < BOARDS && cell < BOARDS => Some(board * BOARDS + cell),
+            (&[cell], Some(board)) if cell < BOARDS => Some(board * BOARDS + cell),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Ultimate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Ultimate {
+    /// Draws the big board. Cells that can be played right now show their
+    /// number within their small board, other empty cells a dot.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let playable = self.get_all_moves();
+        for row in 0..BOARDS {
+            if row > 0 && row % 3 == 0 {
+                writeln!(f, "------+-------+------")?;
+            }
+            let mut line = String::new();
+            for column in 0..BOARDS {
+                if column > 0 {
+                    line.push_str(if column % 3 == 0 { " | " } else { " " });
+                }
+                let board = row / 3 * 3 + column / 3;
+                let cell = row % 3 * 3 + column % 3;
+                let mv = board * BOARDS + cell;
+                line.push(match self.cell(mv) {
+                    Cell::Occupied(player) => self.meta.symbol(player),
+                    Cell::Empty if playable.contains(&mv) => {
+                        char::from_digit(cell as u32, 10).expect("cells are single digits")
+                    }
+                    Cell::Empty => '.',
+                });
+            }
+            writeln!(f, "{}", line)?;
+        }
+        for player in [Player::One, Player::Two] {
+            let won: Vec<String> = self
+                .meta
+                .stones(player)
+                .iter()
+                .map(|board| board.to_string())
+                .collect();
+            if !won.is_empty() {
+                writeln!(
+                    f,
+                    "{} won board {}",
+                    self.meta.symbol(player),
+                    won.join(", ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Ultimate {
+    /// Scores the position from [`Player::One`]'s point of view, like
+    /// [`TicTacToe::evaluate`]: won games score [`WIN_SCORE`] minus the
+    /// moves played, drawn ones `0`.
+    ///
+    /// The heuristic values won small boards and lines on the meta board
+    /// that are still open, weighting boards by how many meta lines they
+    /// are on, and adds the open lines within undecided small boards.
+    pub fn evaluate(&self) -> i32 {
+        match self.game_over() {
+            GameOver::Draw => return 0,
+            GameOver::Winner(player) => return self.win_score(player, 0),
+            GameOver::OnGoing => {}
+        }
+
+        let mut score = 0;
+        let mut drawn = Bitboard::EMPTY;
+        for (board, weight) in PLACE_WEIGHTS.into_iter().enumerate() {
+            score += match (self.meta.cell(board), self.boards[board].game_over()) {
+                (Cell::Occupied(player), _) => sign(player) * BOARD_WEIGHT * weight,
+                (Cell::Empty, GameOver::OnGoing) => {
+                    weight * open_lines(&self.boards[board], Bitboard::EMPTY)
+                }
+                (Cell::Empty, _) => {
+                    drawn.set(board);
+                    0
+                }
+            };
+        }
+        score + BOARD_WEIGHT / 4 * open_lines(&self.meta, drawn)
+    }
+
+    fn win_score(&self, winner: Player, plies: i32) -> i32 {
+        sign(winner) * (WIN_SCORE - self.move_count() as i32 - plies)
+    }
+
+    /// Searches for the best move for the side to move, deepening until
+    /// `time_budget` runs out or the result is certain.
+    pub fn search(&mut self, time_budget: Duration) -> Option<usize> {
+        search::search(self, u32::MAX, Some(time_budget)).best_move
+    }
+
+    /// The engine's move with [`DEFAULT_TIME_BUDGET`] to think.
+    pub fn best_move(&mut self) -> Option<usize> {
+        self.search(DEFAULT_TIME_BUDGET)
+    }
+
+    /// Picks a move the way [`TicTacToe::computer_move`] does for the same
+    /// difficulty. Even [`Difficulty::Perfect`] only searches as far as its
+    /// time allows, as the game is too big to solve.
+    pub fn computer_move(&mut self, difficulty: Difficulty, rng: &mut Rng) -> Option<usize> {
+        match difficulty {
+            Difficulty::Random => rng.choose(&self.get_all_moves()).copied(),
+            Difficulty::Easy => search::search(self, EASY_DEPTH, None).best_move,
+            Difficulty::Medium => {
+                if rng.chance(MEDIUM_BLUNDER_PERCENT, 100) {
+                    self.computer_move(Difficulty::Random, rng)
+                } else {
+                    self.best_move()
+                }
+            }
+            Difficulty::Perfect => self.best_move(),
+        }
+    }
+}
+
+impl Position for Ultimate {
+    fn to_move(&self) -> Player {
+        self.side_to_move
+    }
+
+    fn is_over(&self) -> bool {
+        self.game_over() != GameOver::OnGoing
+    }
+
+    fn search_moves(&self) -> Vec<usize> {
+        self.get_all_moves()
+    }
+
+    fn play(&mut self, mv: usize) {
+        self.make_move(mv);
+    }
+
+    fn take_back(&mut self, _mv: usize) {
+        self.undo_move();
+    }
+
+    fn evaluate(&self) -> i32 {
+        Ultimate::evaluate(self)
+    }
+
+    fn plies_left(&self) -> u32 {
+        (BOARDS * BOARDS - self.move_count()) as u32
+    }
+}
+
+fn sign(player: Player) -> i32 {
+    match player {
+        Player::One => 1,
+        Player::Two => -1,
+    }
+}
+
+/// Counts the lines of `board` only one player has marks on, weighting
+/// them by how many marks that is, from [`Player::One`]'s point of view.
+/// Cells in `blocked` count as taken by both players.
+fn open_lines(board: &TicTacToe, blocked: Bitboard) -> i32 {
+    let stones = [board.stones(Player::One), board.stones(Player::Two)];
+    let mut score = 0;
+    for mask in board.win_masks() {
+        if !(*mask & blocked).is_empty() {
+            continue;
+        }
+        let counts = [(stones[0] & *mask).count(), (stones[1] & *mask).count()];
+        score += match counts {
+            [0, 0] => 0,
+            [own, 0] => 1 << (3 * (own - 1)),
+            [0, other] => -(1 << (3 * (other - 1))),
+            _ => 0,
+        };
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(moves: &[usize]) -> Ultimate {
+        let mut ultimate = Ultimate::new();
+        for &mv in moves {
+            assert!(
+                ultimate.is_move_valid(mv),
+                "{} after {:?}",
+                mv,
+                ultimate.moves()
+            );
+            ultimate.make_move(mv);
+        }
+        ultimate
+    }
+
+    /// O wins board 0 with its middle row, then X sends O back there.
+    const BOARD_0_WON: [usize; 13] = [1, 9, 0, 3, 27, 4, 36, 8, 72, 6, 54, 5, 45];
+
+    #[test]
+    fn sends_the_opponent_to_the_board_of_the_cell() {
+        let ultimate = play(&[4]);
+        assert_eq!(ultimate.forced_board(), Some(4));
+        assert!(ultimate.is_move_valid(4 * BOARDS));
+        assert!(!ultimate.is_move_valid(BOARDS));
+        let moves = ultimate.get_all_moves();
+        assert_eq!(moves.len(), BOARDS);
+        assert!(moves.iter().all(|&mv| mv / BOARDS == 4));
+    }
+
+    #[test]
+    fn a_decided_board_lets_the_opponent_play_anywhere() {
+        let ultimate = play(&BOARD_0_WON);
+        assert_eq!(ultimate.meta().cell(0), Cell::Occupied(Player::Two));
+        assert!(ultimate.is_board_decided(0));
+        assert_eq!(ultimate.forced_board(), None);
+        assert!(ultimate.is_move_valid(BOARDS + 1));
+        assert!(!ultimate.is_move_valid(2));
+        let moves = ultimate.get_all_moves();
+        assert!(moves.iter().all(|&mv| mv >= BOARDS));
+        assert!(moves.iter().any(|&mv| mv / BOARDS == 8));
+    }
+
+    #[test]
+    fn undo_restores_the_forced_board_and_the_meta_board() {
+        let mut ultimate = play(&BOARD_0_WON);
+        assert_eq!(ultimate.undo_move(), Some(45));
+        assert_eq!(ultimate.forced_board(), Some(5));
+        assert_eq!(ultimate.undo_move(), Some(5));
+        assert_eq!(ultimate.forced_board(), Some(0));
+        assert_eq!(ultimate.meta().cell(0), Cell::Empty);
+        assert_eq!(ultimate.board(0).cell(5), Cell::Empty);
+        assert_eq!(ultimate.turn_to_move(), Player::Two);
+
+        // Taking back every move of random games restores each position.
+        let mut rng = Rng::new(5);
+        for _ in 0..20 {
+            let mut ultimate = Ultimate::new();
+            let mut positions = Vec::new();
+            while let Some(&mv) = rng.choose(&ultimate.get_all_moves()) {
+                positions.push((ultimate.to_string(), ultimate.forced_board()));
+                ultimate.make_move(mv);
+            }
+            while let Some((board, forced)) = positions.pop() {
+                ultimate.undo_move();
+                assert_eq!(
+                    (ultimate.to_string(), ultimate.forced_board()),
+                    (board, forced)
+                );
+            }
+            assert_eq!(ultimate.undo_move(), None);
+        }
+    }
+
+    #[test]
+    fn draws_when_every_board_is_decided() {
+        let mut rng = Rng::new(1);
+        let mut draws = 0;
+        for _ in 0..200 {
+            let mut ultimate = Ultimate::new();
+            while let Some(&mv) = rng.choose(&ultimate.get_all_moves()) {
+                ultimate.make_move(mv);
+            }
+            let decided = (0..BOARDS).all(|board| ultimate.is_board_decided(board));
+            match ultimate.game_over() {
+                GameOver::Draw => {
+                    assert!(decided);
+                    assert!(!matches!(ultimate.meta().game_over(), GameOver::Winner(_)));
+                    draws += 1;
+                }
+                GameOver::Winner(player) => {
+                    assert_eq!(ultimate.meta().game_over(), GameOver::Winner(player));
+                }
+                GameOver::OnGoing => panic!("a game without moves is not over"),
+            }
+        }
+        assert!(draws > 0);
+    }
+
+    #[test]
+    fn parses_moves() {
+        let ultimate = Ultimate::new();
+        assert_eq!(ultimate.parse_move("4 0"), Some(36));
+        assert_eq!(ultimate.parse_move(" 8, 8 "), Some(80));
+        assert_eq!(ultimate.parse_move("4"), None);
+        assert_eq!(ultimate.parse_move("9 0"), None);
+        assert_eq!(ultimate.parse_move("1 2 3"), None);
+        assert_eq!(ultimate.parse_move("a b"), None);
+        assert_eq!(ultimate.parse_move(""), None);
+
+        let ultimate = play(&[4]);
+        assert_eq!(ultimate.parse_move("2"), Some(38));
+        assert_eq!(ultimate.parse_move("0 2"), Some(2));
+        assert_eq!(ultimate.parse_move("9"), None);
+    }
+}
@@ -0,0 +1,348 @@
+//! The server side of the WebSocket protocol (RFC 6455): the opening
+//! handshake and reading and writing single frames. Fragmented messages and
+//! extensions are not supported.
+
+use std::io::{self, Read, Write};
+
+use crate::http::Request;
+
+/// Appended to the client's key before hashing, see RFC 6455 section 1.3.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest frame payload that is read.
+const MAX_PAYLOAD: u64 = 64 * 1024;
+
+/// One WebSocket frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close,
+}
+
+/// Whether `request` asks to switch to the WebSocket protocol.
+pub fn is_upgrade(request: &Request) -> bool {
+    request
+        .header("upgrade")
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+}
+
+/// The `101 Switching Protocols` response accepting `request`, or `None` if
+/// it is not a valid WebSocket handshake.
+pub fn handshake(request: &Request) -> Option<String> {
+    if request.method != "GET" || !is_upgrade(request) {
+        return None;
+    }
+    if request.header("sec-websocket-version") != Some("13") {
+        return None;
+    }
+    let key = request.header("sec-websocket-key")?;
+    Some(format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    ))
+}
+
+/// The `Sec-WebSocket-Accept` value for a client's `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(
+        format!("{}{}", key.trim(), HANDSHAKE_GUID).as_bytes(),
+    ))
+}
+
+/// Writes `frame` unmasked, as servers do.
+pub fn write_frame(writer: &mut impl Write, frame: &Frame) -> io::Result<()> {
+    let (opcode, payload): (u8, &[u8]) = match frame {
+        Frame::Text(text) => (0x1, text.as_bytes()),
+        Frame::Binary(data) => (0x2, data),
+        Frame::Close => (0x8, &[]),
+        Frame::Ping(data) => (0x9, data),
+        Frame::Pong(data) => (0xA, data),
+    };
+    let mut header = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => header.push(len as u8),
+        len @ 126..=0xFFFF => {
+            header.push(126);
+            header.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            header.push(127);
+            header.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    writer.write_all(&header)?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+/// Reads one frame sent by a client, unmasking its payload.
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Frame> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header)?;
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    let length = match header[1] & 0x7F {
+        126 => {
+            let mut bytes = [0; 2];
+            reader.read_exact(&mut bytes)?;
+            u16::from_be_bytes(bytes) as u64
+        }
+        127 => {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            u64::from_be_bytes(bytes)
+        }
+        len => len as u64,
+    };
+    if length > MAX_PAYLOAD {
+        return Err(invalid("frame too large"));
+    }
+    let mut mask = [0; 4];
+    if masked {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; length as usize];
+    reader.read_exact(&mut payload)?;
+    if masked {
+        for (index, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[index % 4];
+        }
+    }
+    match opcode {
+        0x1 => String::from_utf8(payload)
+            .map(Frame::Text)
+            .map_err(|_| invalid("text frame is not UTF-8")),
+        0x2 => Ok(Frame::Binary(payload)),
+        0x8 => Ok(Frame::Close),
+        0x9 => Ok(Frame::Ping(payload)),
+        0xA => Ok(Frame::Pong(payload)),
+        _ => Err(invalid("unsupported frame")),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// SHA-1 (FIPS 180-4) of `data`. Only used for the handshake, where its
+/// weakness does not matter.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut words = [0u32; 80];
+        for (index, word) in block.chunks_exact(4).enumerate() {
+            words[index] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for index in 16..80 {
+            words[index] =
+                (words[index - 3] ^ words[index - 8] ^ words[index - 14] ^ words[index - 16])
+                    .rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (index, &word) in words.iter().enumerate() {
+            let (f, k) = match index {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (value, new) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(new);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, value) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+/// Standard base64 with padding.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * index) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn computes_the_accept_key() {
+        // The example in RFC 6455 section 1.3.
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn hashes_with_sha1() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(&sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn encodes_base64() {
+        for (data, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(data.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn answers_the_handshake() {
+        let request = Request::read(
+            &mut "GET /games/1/watch HTTP/1.1\r\n\
+                  Upgrade: websocket\r\n\
+                  Connection: Upgrade\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                  Sec-WebSocket-Version: 13\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(is_upgrade(&request));
+        let response = handshake(&request).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        let mut old_version = request.clone();
+        old_version
+            .headers
+            .retain(|(name, _)| name != "sec-websocket-version");
+        assert_eq!(handshake(&old_version), None);
+    }
+
+    #[test]
+    fn reads_masked_frames() {
+        // The masked "Hello" of RFC 6455 section 5.7.
+        let frame = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+        assert_eq!(
+            read_frame(&mut &frame[..]).unwrap(),
+            Frame::Text("Hello".to_string())
+        );
+    }
+
+    #[test]
+    fn writes_unmasked_frames() {
+        let mut written = Vec::new();
+        write_frame(&mut written, &Frame::Text("Hello".to_string())).unwrap();
+        assert_eq!(written, [0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]);
+        assert_eq!(
+            read_frame(&mut &written[..]).unwrap(),
+            Frame::Text("Hello".to_string())
+        );
+    }
+
+    #[test]
+    fn uses_the_16_bit_length() {
+        let payload = vec![7; 300];
+        let mut written = Vec::new();
+        write_frame(&mut written, &Frame::Binary(payload.clone())).unwrap();
+        assert_eq!(written[..4], [0x82, 126, 0x01, 0x2c]);
+        assert_eq!(written.len(), 4 + 300);
+        assert_eq!(
+            read_frame(&mut &written[..]).unwrap(),
+            Frame::Binary(payload)
+        );
+    }
+
+    #[test]
+    fn uses_the_64_bit_length() {
+        let payload = vec![7; 0x10000];
+        let mut written = Vec::new();
+        write_frame(&mut written, &Frame::Binary(payload.clone())).unwrap();
+        assert_eq!(written[..10], [0x82, 127, 0, 0, 0, 0, 0, 1, 0, 0]);
+        assert_eq!(written.len(), 10 + 0x10000);
+        assert_eq!(
+            read_frame(&mut &written[..]).unwrap(),
+            Frame::Binary(payload)
+        );
+
+        // A masked ping with its length in the 64-bit form.
+        let mut frame = vec![0x89, 0x80 | 127, 0, 0, 0, 0, 0, 0, 0, 2];
+        frame.extend_from_slice(&[1, 2, 3, 4]);
+        frame.extend_from_slice(&[b'h' ^ 1, b'i' ^ 2]);
+        assert_eq!(
+            read_frame(&mut &frame[..]).unwrap(),
+            Frame::Ping(b"hi".to_vec())
+        );
+    }
+
+    #[test]
+    fn rejects_bad_frames() {
+        let too_large = [0x82, 127, 0, 0, 0, 0, 0, 1, 0, 1];
+        assert!(read_frame(&mut &too_large[..]).is_err());
+        let truncated = [0x81, 0x85, 0x37, 0xfa];
+        assert!(read_frame(&mut &truncated[..]).is_err());
+        let not_utf8 = [0x81, 0x01, 0xff];
+        assert!(read_frame(&mut &not_utf8[..]).is_err());
+        let continuation = [0x00, 0x00];
+        assert!(read_frame(&mut &continuation[..]).is_err());
+    }
+}